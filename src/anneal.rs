@@ -0,0 +1,83 @@
+use rand::Rng;
+use std::fmt;
+
+use crate::ising::Ising;
+
+/// Simulated annealing with a geometric temperature ladder from `t_start` to `t_end`.
+#[derive(Debug)]
+pub struct Annealer {
+    pub t_start: f64,
+    pub t_end: f64,
+    pub sweeps: usize,
+    pub restarts: usize,
+}
+
+/// Lowest energy configuration found by a solver.
+#[derive(Debug)]
+pub struct Solution {
+    pub energy: f64,
+    pub spins: Vec<i8>,
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "energy: {}", self.energy)?;
+        let spins: Vec<String> = self.spins.iter().map(|s| s.to_string()).collect();
+        write!(f, "spins: {}", spins.join(" "))
+    }
+}
+
+impl Default for Annealer {
+    fn default() -> Self {
+        Self {
+            t_start: 3.0,
+            t_end: 0.05,
+            sweeps: 1000,
+            restarts: 4,
+        }
+    }
+}
+
+impl Annealer {
+    /// Anneals `ising` from a fresh random configuration `restarts` times, finishing each
+    /// run with a zero temperature descent, and keeps the best configuration seen.
+    pub fn run<R: Rng + ?Sized>(&self, ising: &mut Ising, rng: &mut R) -> Solution {
+        let num_spins = ising.lattice.len();
+        let mut best = Solution {
+            energy: f64::INFINITY,
+            spins: ising.lattice.clone(),
+        };
+
+        for _ in 0..self.restarts.max(1) {
+            ising.randomize(rng);
+            let mut energy = ising.total_energy();
+
+            for sweep in 0..self.sweeps {
+                let t = sweep as f64 / (self.sweeps.max(2) - 1) as f64;
+                ising.temperature = self.t_start * (self.t_end / self.t_start).powf(t);
+                for _ in 0..num_spins {
+                    energy += ising.monte_carlo_step(rng);
+                }
+                best.update(energy, &ising.lattice);
+            }
+
+            energy += ising.descend();
+            best.update(energy, &ising.lattice);
+        }
+
+        // Leave the system in the best configuration, with an energy free of rounding drift
+        ising.lattice.copy_from_slice(&best.spins);
+        best.energy = ising.total_energy();
+        best
+    }
+}
+
+impl Solution {
+    /// Keeps the configuration if it improves on the best energy so far.
+    pub fn update(&mut self, energy: f64, spins: &[i8]) {
+        if energy < self.energy {
+            self.energy = energy;
+            self.spins.copy_from_slice(spins);
+        }
+    }
+}
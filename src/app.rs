@@ -1,12 +1,41 @@
 use crate::ui::ui;
-use crate::ising::Ising;
+use crate::avalanche::FieldSweep;
+use crate::cli::Options;
+use crate::cluster::Algorithm;
+use crate::disorder::{self, Distribution, Histogram, RandomField};
+use crate::exact::{Enumeration, Sampler, Thermodynamics, MAX_ENUMERATION_SITES};
+use crate::heisenberg::HeisenbergSpins;
+use crate::ising::{Ising, Model};
 use crate::parameter::{Parameter, ParameterType};
+use crate::planar::PlanarSpins;
+use crate::reweighting::{self, Recorder, Reweighting};
+use crate::schedule::Schedule;
+use crate::structure;
+use crate::tempering::ParallelTempering;
+use crate::wang_landau::DensityOfStates;
 
+use std::collections::VecDeque;
 use std::io;
 use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use rand::rngs::ThreadRng;
 use ratatui::{backend::Backend, Terminal};
 
+/// Frames of measurements kept for the charts.
+const HISTORY_LENGTH: usize = 2000;
+/// Moves attempted by the Wang-Landau walk per frame.
+const WANG_LANDAU_STEPS: usize = 1 << 18;
+/// File the density of states is exported to without --output.
+const DENSITY_OF_STATES_PATH: &str = "density_of_states.txt";
+/// File the recorded series are exported to without --output.
+const SERIES_PATH: &str = "series.txt";
+/// Samples recorded between reweightings of the series.
+const REWEIGHT_INTERVAL: usize = 256;
+/// Iterations of the multi-histogram equations per frame, and the change of ln Z at which
+/// they count as solved for the curves drawn.
+const REWEIGHT_ITERATIONS: usize = 1;
+const REWEIGHT_TOLERANCE: f64 = 1e-5;
+/// Temperatures the reweighted curves are drawn at.
+const REWEIGHTED_POINTS: usize = 100;
 
 #[derive(Debug, PartialEq)]
 pub enum Page {
@@ -15,6 +44,16 @@ pub enum Page {
     Exit,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum View {
+    Braille,
+    /// One colored half block per sampled site, which can show vacancies. Heisenberg
+    /// spins are shaded by their z component.
+    Color,
+    /// Heisenberg spins colored by their angle in the xy plane.
+    Angle,
+}
+
 #[derive(Debug)]
 pub enum MCOrder {
     Linear,
@@ -27,61 +66,399 @@ pub struct App {
     pub page: Page,
     pub current_parameter: ParameterType,
     pub ising: Ising,
+    /// Second copy on the same disorder, for the spin glass overlap.
+    pub replica: Option<Ising>,
+    pub dilution: f64,
+    pub bond_disorder: Distribution,
+    pub random_field: Option<RandomField>,
+    pub field_sweep: Option<FieldSweep>,
+    /// XY or clock rotors, run instead of the spins of `ising`.
+    pub planar: Option<PlanarSpins>,
+    /// Heisenberg spins, run instead of the spins of `ising`.
+    pub heisenberg: Option<HeisenbergSpins>,
+    /// Replicas at a ladder of temperatures, the coldest of which is shown as `ising`.
+    pub tempering: Option<ParallelTempering>,
+    /// Wang-Landau walk run instead of the dynamics, its configuration shown as `ising`.
+    pub density_of_states: Option<DensityOfStates>,
+    pub output: Option<String>,
+    /// Outcome of the last export of the density of states or recorded series.
+    pub export: Option<String>,
     pub mc_order: MCOrder,
+    pub algorithm: Algorithm,
+    pub view: View,
+    /// z plane drawn from a three dimensional lattice.
+    pub slice: usize,
     pub thread_rng: ThreadRng,
     pub paused: bool,
     pub temp_param: Parameter,
-    pub coupling_param: Parameter,
+    pub horizontal_coupling_param: Parameter,
+    pub vertical_coupling_param: Parameter,
+    /// Gauges for the competing bonds, listed only when the lattice has them.
+    pub diagonal_coupling_param: Option<Parameter>,
+    pub axial_coupling_param: Option<Parameter>,
+    /// Gauge for the Blume-Capel crystal field.
+    pub crystal_field_param: Option<Parameter>,
+    pub anisotropy_param: Option<Parameter>,
     pub mag_moment_param: Parameter,
     pub mag_field_strength_param: Parameter,
+    pub disorder_param: Parameter,
+    pub schedule: Option<Schedule>,
     pub magnetization: f64,
     pub energy: f64,
+    pub overlap: f64,
+    /// Dominant wavevector (q_x, q_y) / π, tracked with competing bonds.
+    pub modulation: Option<(f64, f64)>,
+    pub overlap_histogram: Histogram,
+    /// Exact solution of lattices small enough to enumerate, kept up to date with the
+    /// couplings and disorder, and the Monte Carlo averages it is compared with.
+    pub exact: Option<Enumeration>,
+    pub sampler: Sampler,
+    /// Recent (T, m, E/N) measurements, charted against the exact solutions.
+    pub history: VecDeque<(f64, f64, f64)>,
+    /// (E, M) series of Ising and Blume-Capel spins at every temperature and field held.
+    pub recorder: Recorder,
+    /// Multi-histogram equations of the series, solved an iteration per frame from
+    /// the previous solution, and whether the curves below are drawn from them yet.
+    pub reweighting: Option<Reweighting>,
+    pub reweighting_solved: bool,
+    /// Thermodynamics reweighted from the series over the temperatures they reach, in
+    /// the current field, and the peak (T, χ) of the susceptibility.
+    pub reweighted: Vec<(f64, Thermodynamics)>,
+    pub susceptibility_peak: Option<(f64, f64)>,
 }
 
 impl App {
-    pub fn new(size: usize) -> Self {
-        Self {
+    /// Builds the interactive view, or fails if the --graph cannot be built.
+    pub fn new(options: Options) -> Result<Self, String> {
+        let mut rng = options.rng();
+        let mut ising = Ising::new(options.lattice(&mut rng)?, &mut rng);
+        if let Some(states) = options.potts {
+            ising.model = Model::Potts { states };
+            ising.randomize(&mut rng);
+        }
+        if options.crystal_field.is_some() {
+            ising.model = Model::BlumeCapel;
+            ising.randomize(&mut rng);
+        }
+        if options.dilution > 0.0 {
+            ising.dilute(options.dilution, &mut rng);
+        }
+        let mut replica = None;
+        if let Some(distribution) = options.disorder {
+            disorder::randomize_bonds(&mut ising, distribution, &mut rng);
+            let mut copy = ising.clone();
+            copy.randomize(&mut rng);
+            replica = Some(copy);
+        }
+        let competing = options.diagonal_coupling.is_some() || options.axial_coupling.is_some();
+        let competing_param = |coupling: f64| {
+            let mut param = Parameter::new_signed(1024, 4, (0, 2048), 1.0);
+            param.set_scaled(coupling);
+            param
+        };
+        let planar = options
+            .is_planar()
+            .then(|| PlanarSpins::new(&ising, options.clock, &mut rng));
+        let heisenberg = options
+            .heisenberg
+            .then(|| HeisenbergSpins::new(&ising, options.anisotropy, &mut rng));
+        let tempering = options
+            .tempering
+            .clone()
+            .map(|ladder| ParallelTempering::new(&ising, ladder, &mut rng));
+        let density_of_states = options
+            .density_of_states
+            .then(|| DensityOfStates::new(&ising, options.wang_landau, &mut rng));
+        let small = ising.lattice.len() <= MAX_ENUMERATION_SITES;
+        let exact = (small && !options.is_planar() && !options.heisenberg)
+            .then(|| Enumeration::new(&ising).ok())
+            .flatten();
+        let random_field = options
+            .random_field
+            .map(|distribution| RandomField::new(distribution, ising.lattice.len(), &mut rng));
+
+        Ok(Self {
             page: Page::Main,
             current_parameter: ParameterType::Temp,
-            ising: Ising::new(size),
+            ising,
+            replica,
+            dilution: options.dilution,
+            bond_disorder: options.disorder.unwrap_or(Distribution::Bimodal),
+            random_field,
+            field_sweep: None,
+            planar,
+            heisenberg,
+            tempering,
+            density_of_states,
+            output: options.output.clone(),
+            export: None,
             mc_order: MCOrder::Linearithmic,
+            algorithm: options.algorithm,
+            // Braille shows only two states
+            view: if options.dilution > 0.0
+                || options.potts.is_some()
+                || options.crystal_field.is_some()
+                || options.is_planar()
+                || options.heisenberg
+            {
+                View::Color
+            } else {
+                View::Braille
+            },
+            slice: 0,
             thread_rng: ThreadRng::default(),
             paused: false,
             temp_param: Parameter::new(1024, 4, (0, 1024), 2.269 * 2.0),
-            coupling_param: Parameter::new(1024, 4, (0, 1024), 1.0),
+            horizontal_coupling_param: Parameter::new_signed(2048, 4, (0, 2048), 1.0),
+            vertical_coupling_param: Parameter::new_signed(2048, 4, (0, 2048), 1.0),
+            diagonal_coupling_param: options.diagonal_coupling.map(competing_param),
+            axial_coupling_param: options.axial_coupling.map(competing_param),
+            crystal_field_param: options.crystal_field.map(|crystal_field| {
+                let mut param = Parameter::new_signed(1024, 4, (0, 2048), 3.0);
+                param.set_scaled(crystal_field);
+                param
+            }),
+            anisotropy_param: options.heisenberg.then(|| {
+                let mut param = Parameter::new_signed(1024, 4, (0, 2048), 3.0);
+                param.set_scaled(options.anisotropy);
+                param
+            }),
             mag_moment_param: Parameter::new(0, 4, (0, 1024), 0.1),
             mag_field_strength_param: Parameter::new(0, 4, (0, 1024), 1.0),
+            disorder_param: Parameter::new(256, 4, (0, 1024), 4.0),
+            schedule: options.schedule,
             magnetization: 0.0,
             energy: 0.0,
+            overlap: 0.0,
+            modulation: competing.then_some((0.0, 0.0)),
+            overlap_histogram: Histogram::new(21),
+            exact,
+            sampler: Sampler::default(),
+            history: VecDeque::with_capacity(HISTORY_LENGTH),
+            recorder: Recorder::default(),
+            reweighting: None,
+            reweighting_solved: false,
+            reweighted: Vec::new(),
+            susceptibility_peak: None,
+        })
+    }
+
+    /// Gauges currently on screen, top to bottom.
+    pub fn parameters(&self) -> Vec<ParameterType> {
+        let mut parameters = vec![
+            ParameterType::Temp,
+            ParameterType::HorizontalCoupling,
+            ParameterType::VerticalCoupling,
+        ];
+        if self.diagonal_coupling_param.is_some() {
+            parameters.push(ParameterType::DiagonalCoupling);
+        }
+        if self.axial_coupling_param.is_some() {
+            parameters.push(ParameterType::AxialCoupling);
+        }
+        if self.crystal_field_param.is_some() {
+            parameters.push(ParameterType::CrystalField);
+        }
+        if self.anisotropy_param.is_some() {
+            parameters.push(ParameterType::Anisotropy);
+        }
+        parameters.extend([ParameterType::MagMoment, ParameterType::MagFieldStrength]);
+        if self.random_field.is_some() {
+            parameters.push(ParameterType::Disorder);
+        }
+        parameters
+    }
+
+    /// Whether the discrete spins of `ising` are the ones simulated, rather than rotors or
+    /// Heisenberg spins.
+    pub fn has_ising_spins(&self) -> bool {
+        self.planar.is_none() && self.heisenberg.is_none()
+    }
+
+    /// Whether the spins shown sample the canonical ensemble of Ising or Blume-Capel
+    /// spins at the set temperature and field, as histogram reweighting needs.
+    pub fn is_recording(&self) -> bool {
+        let magnetic = !matches!(self.ising.model, Model::Potts { .. });
+        self.has_ising_spins() && magnetic && self.field_sweep.is_none() && self.density_of_states.is_none()
+    }
+
+    /// Advances the reweighting of the recorded series, and once solved redraws the
+    /// curves over the temperatures they reach, in the current field.
+    fn reweight(&mut self) {
+        let field = self.ising.magnetic_moment * self.ising.magnetic_field_strength;
+        let Some(reweighting) = &mut self.reweighting else {
+            return;
+        };
+        if self.reweighting_solved || !reweighting.iterate(REWEIGHT_ITERATIONS, REWEIGHT_TOLERANCE) {
+            return;
+        }
+        self.reweighting_solved = true;
+        let (lowest, highest) = reweighting.range();
+        self.reweighted = (0..=REWEIGHTED_POINTS)
+            .map(|k| lowest + (highest - lowest) * k as f64 / REWEIGHTED_POINTS as f64)
+            .map(|temperature| (temperature, reweighting.thermodynamics(temperature, field)))
+            .collect();
+        self.susceptibility_peak = Some(reweighting.maximum(field, |estimate| estimate.susceptibility));
+    }
+
+    /// Draws new vacancies, bonds and random fields for whichever disorder is enabled.
+    fn reseed_disorder(&mut self) {
+        if self.dilution > 0.0 {
+            self.ising.dilute(self.dilution, &mut self.thread_rng);
+        }
+        if self.replica.is_some() {
+            disorder::randomize_bonds(&mut self.ising, self.bond_disorder, &mut self.thread_rng);
+            let mut copy = self.ising.clone();
+            copy.randomize(&mut self.thread_rng);
+            self.replica = Some(copy);
+            self.overlap_histogram.clear();
+        }
+        if let Some(random_field) = &mut self.random_field {
+            random_field.reseed(&mut self.thread_rng);
+        }
+    }
+
+    fn parameter_mut(&mut self, parameter: ParameterType) -> &mut Parameter {
+        match parameter {
+            ParameterType::Temp => &mut self.temp_param,
+            ParameterType::HorizontalCoupling => &mut self.horizontal_coupling_param,
+            ParameterType::VerticalCoupling => &mut self.vertical_coupling_param,
+            ParameterType::DiagonalCoupling => self.diagonal_coupling_param.as_mut().expect("listed without J2 bonds"),
+            ParameterType::AxialCoupling => self.axial_coupling_param.as_mut().expect("listed without J3 bonds"),
+            ParameterType::CrystalField => self.crystal_field_param.as_mut().expect("listed without Blume-Capel spins"),
+            ParameterType::Anisotropy => self.anisotropy_param.as_mut().expect("listed without Heisenberg spins"),
+            ParameterType::MagMoment => &mut self.mag_moment_param,
+            ParameterType::MagFieldStrength => &mut self.mag_field_strength_param,
+            ParameterType::Disorder => &mut self.disorder_param,
         }
     }
 
     pub fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
         loop {
             if self.page == Page::Main {
-                let n = &self.ising.size;
-                let n_steps: usize;
-                match self.mc_order {
-                    MCOrder::Linear => {
-                        n_steps = *n;
-                    }
-                    MCOrder::Linearithmic => {
-                        n_steps = n * (*n as f64).log2() as usize;
+                // side length of a square lattice with as many sites
+                let n = (self.ising.lattice.len() as f64).sqrt() as usize;
+                let n_steps = match self.mc_order {
+                    MCOrder::Linear => n,
+                    MCOrder::Linearithmic => n * (n as f64).log2() as usize,
+                    MCOrder::Quadratic => n.pow(2),
+                };
+
+                self.ising.temperature = match (&self.schedule, &self.tempering) {
+                    (_, Some(tempering)) => tempering.temperatures[0],
+                    // Held at its last temperature once done, which the gauge may not reach
+                    (Some(schedule), _) => {
+                        let temperature = schedule.temperature();
+                        self.temp_param.set_scaled(temperature);
+                        temperature
                     }
-                    MCOrder::Quadratic => {
-                        n_steps = n.pow(2);
+                    _ => self.temp_param.scaled(),
+                };
+                self.ising.horizontal_coupling = self.horizontal_coupling_param.scaled();
+                self.ising.vertical_coupling = self.vertical_coupling_param.scaled();
+                self.ising.diagonal_coupling = self.diagonal_coupling_param.as_ref().map_or(0.0, Parameter::scaled);
+                self.ising.axial_coupling = self.axial_coupling_param.as_ref().map_or(0.0, Parameter::scaled);
+                self.ising.crystal_field = self.crystal_field_param.as_ref().map_or(0.0, Parameter::scaled);
+                if let (Some(heisenberg), Some(param)) = (&mut self.heisenberg, &self.anisotropy_param) {
+                    heisenberg.anisotropy = param.scaled();
+                }
+                // The field sweep drives the uniform field itself
+                if self.field_sweep.is_none() {
+                    self.ising.magnetic_moment = self.mag_moment_param.scaled();
+                    self.ising.magnetic_field_strength = self.mag_field_strength_param.scaled();
+                }
+                if let Some(random_field) = &self.random_field {
+                    random_field.apply(&mut self.ising, self.disorder_param.scaled());
+                }
+                if let Some(replica) = &mut self.replica {
+                    if replica.temperature != self.ising.temperature {
+                        self.overlap_histogram.clear();
                     }
+                    replica.temperature = self.ising.temperature;
+                    replica.horizontal_coupling = self.ising.horizontal_coupling;
+                    replica.vertical_coupling = self.ising.vertical_coupling;
+                    replica.diagonal_coupling = self.ising.diagonal_coupling;
+                    replica.axial_coupling = self.ising.axial_coupling;
+                    replica.crystal_field = self.ising.crystal_field;
+                    replica.magnetic_moment = self.ising.magnetic_moment;
+                    replica.magnetic_field_strength = self.ising.magnetic_field_strength;
+                    replica.fields.copy_from_slice(&self.ising.fields);
                 }
 
-                self.ising.temperature = self.temp_param.scaled();
-                self.ising.coupling_constant = self.coupling_param.scaled();
-                self.ising.magnetic_moment = self.mag_moment_param.scaled();
-                self.ising.magnetic_field_strength = self.mag_field_strength_param.scaled();
-
                 if !self.paused {   
-                    for _ in 0..n_steps {
-                        self.ising.monte_carlo_step(&mut self.thread_rng);
+                    if let Some(field_sweep) = &mut self.field_sweep {
+                        for _ in 0..(n_steps / n).max(1) {
+                            field_sweep.step(&mut self.ising);
+                        }
+                    } else if let Some(planar) = &mut self.planar {
+                        planar.run(&self.ising, n_steps, &mut self.thread_rng);
+                        planar.measure(&self.ising);
+                    } else if let Some(heisenberg) = &mut self.heisenberg {
+                        heisenberg.run(&self.ising, n_steps, &mut self.thread_rng);
+                    } else if let Some(tempering) = &mut self.tempering {
+                        // One sweep of every replica per frame
+                        tempering.algorithm = self.algorithm;
+                        tempering.sync(&self.ising);
+                        tempering.sweep(&mut self.thread_rng);
+                        self.ising.lattice.copy_from_slice(&tempering.replicas[0].lattice);
+                    } else if let Some(density_of_states) = &mut self.density_of_states {
+                        if !density_of_states.matches(&self.ising) {
+                            let settings = density_of_states.settings;
+                            *density_of_states = DensityOfStates::new(&self.ising, settings, &mut self.thread_rng);
+                        }
+                        for _ in 0..(WANG_LANDAU_STEPS / self.ising.lattice.len()).max(1) {
+                            density_of_states.sweep(&mut self.thread_rng);
+                        }
+                        self.ising.lattice.copy_from_slice(&density_of_states.walker.lattice);
+                    } else {
+                        self.algorithm.run(&mut self.ising, n_steps, &mut self.thread_rng);
+                    }
+                    if let Some(replica) = &mut self.replica {
+                        self.algorithm.run(replica, n_steps, &mut self.thread_rng);
+                        self.overlap = disorder::overlap(&self.ising, replica);
+                        self.overlap_histogram.add(self.overlap);
+                    }
+                    if let Some(schedule) = &mut self.schedule {
+                        schedule.advance();
+                    }
+                    // The walker is not at the temperature
+                    if self.exact.is_some() && self.density_of_states.is_none() {
+                        self.sampler.add(&self.ising);
+                    }
+                    if self.is_recording() && self.recorder.add(&self.ising) && self.recorder.samples().is_multiple_of(REWEIGHT_INTERVAL) {
+                        let sites = self.ising.num_occupied();
+                        self.reweighting = Reweighting::start(sites, &self.recorder.series, self.reweighting.as_ref()).ok();
+                        self.reweighting_solved = false;
+                    }
+                    if self.recorder.samples() == 0 {
+                        self.reweighting = None;
+                        self.reweighted.clear();
+                        self.susceptibility_peak = None;
+                    }
+                }
+                self.reweight();
+
+                if let Some(planar) = &self.planar {
+                    self.magnetization = planar.magnetization(&self.ising);
+                    self.energy = planar.energy(&self.ising);
+                } else if let Some(heisenberg) = &self.heisenberg {
+                    self.magnetization = heisenberg.magnetization(&self.ising);
+                    self.energy = heisenberg.energy(&self.ising);
+                } else {
+                    self.magnetization = self.ising.magnetization();
+                    self.energy = self.ising.energy();
+                }
+                if self.modulation.is_some() {
+                    self.modulation = Some(structure::dominant_wavevector(&self.ising));
+                }
+                if !self.paused && self.density_of_states.is_none() {
+                    if self.history.len() == HISTORY_LENGTH {
+                        self.history.pop_front();
                     }
+                    self.history.push_back((self.ising.temperature, self.magnetization, self.energy));
+                }
+                if self.exact.as_ref().is_some_and(|exact| !exact.matches(&self.ising)) {
+                    self.exact = Enumeration::new(&self.ising).ok();
                 }
             }
 
@@ -93,6 +470,47 @@ impl App {
                         Page::Main => match key_event.code {
                             KeyCode::Char('q') => self.page = Page::Exit,
                             KeyCode::Char(' ') => self.paused = !self.paused,
+                            KeyCode::Char('p') => {
+                                if let Some(schedule) = &mut self.schedule {
+                                    schedule.paused = !schedule.paused;
+                                }
+                            }
+                            KeyCode::Char('r') => {
+                                if let Some(schedule) = &mut self.schedule {
+                                    schedule.restart();
+                                    if schedule.is_quench() {
+                                        self.ising.randomize(&mut self.thread_rng);
+                                        if let Some(planar) = &mut self.planar {
+                                            planar.randomize(&mut self.thread_rng);
+                                        }
+                                        if let Some(heisenberg) = &mut self.heisenberg {
+                                            heisenberg.randomize(&mut self.thread_rng);
+                                        }
+                                    }
+                                } else if let Some(tempering) = &mut self.tempering {
+                                    tempering.randomize(&mut self.thread_rng);
+                                    self.ising.lattice.copy_from_slice(&tempering.replicas[0].lattice);
+                                } else if let Some(density_of_states) = &mut self.density_of_states {
+                                    let settings = density_of_states.settings;
+                                    *density_of_states = DensityOfStates::new(&self.ising, settings, &mut self.thread_rng);
+                                }
+                            }
+                            KeyCode::Char('x') => {
+                                if let Some(density_of_states) = &self.density_of_states {
+                                    let path = self.output.as_deref().unwrap_or(DENSITY_OF_STATES_PATH);
+                                    self.export = Some(match density_of_states.write(path) {
+                                        Ok(()) => format!("g(E) written to {}", path),
+                                        Err(e) => e,
+                                    });
+                                } else if self.recorder.samples() > 0 {
+                                    let path = self.output.as_deref().unwrap_or(SERIES_PATH);
+                                    let series = &self.recorder.series;
+                                    self.export = Some(match reweighting::write(path, self.ising.num_occupied(), series) {
+                                        Ok(()) => format!("{} series written to {}", series.len(), path),
+                                        Err(e) => e,
+                                    });
+                                }
+                            }
                             KeyCode::Char('+') => match self.mc_order {
                                 MCOrder::Linear => self.mc_order = MCOrder::Linearithmic,
                                 MCOrder::Linearithmic => self.mc_order = MCOrder::Quadratic,
@@ -103,77 +521,56 @@ impl App {
                                 MCOrder::Linearithmic => self.mc_order = MCOrder::Linear,
                                 MCOrder::Quadratic => self.mc_order = MCOrder::Linearithmic,
                             },
-                            KeyCode::Up => match self.current_parameter {
-                                ParameterType::Temp => {},
-                                ParameterType::Coupling => self.current_parameter = ParameterType::Temp,
-                                ParameterType::MagMoment => self.current_parameter = ParameterType::Coupling,
-                                ParameterType::MagFieldStrength => self.current_parameter = ParameterType::MagMoment,
+                            KeyCode::Char('u') => match (&mut self.planar, &mut self.heisenberg) {
+                                (Some(planar), _) => planar.overrelaxation = !planar.overrelaxation,
+                                (_, Some(heisenberg)) => heisenberg.heat_bath = !heisenberg.heat_bath,
+                                _ => self.algorithm = self.algorithm.next(),
+                            },
+                            // The zero temperature field sweep flips Ising spins
+                            KeyCode::Char('a') if self.has_ising_spins() && self.ising.model == Model::Ising && self.tempering.is_none() && self.density_of_states.is_none() => {
+                                self.field_sweep = match self.field_sweep {
+                                    Some(_) => None,
+                                    None => Some(FieldSweep::new(&mut self.ising)),
+                                };
                             }
-                            KeyCode::Down => match self.current_parameter {
-                                ParameterType::Temp => self.current_parameter = ParameterType::Coupling,
-                                ParameterType::Coupling => self.current_parameter = ParameterType::MagMoment,
-                                ParameterType::MagMoment => self.current_parameter = ParameterType::MagFieldStrength,
-                                ParameterType::MagFieldStrength => {},
+                            KeyCode::Char('d') => self.reseed_disorder(),
+                            // Rotors only have the color view
+                            KeyCode::Char('v') if self.planar.is_none() => {
+                                self.view = match (&self.view, &self.heisenberg) {
+                                    (View::Color, Some(_)) => View::Angle,
+                                    (_, Some(_)) => View::Color,
+                                    (View::Braille, None) => View::Color,
+                                    (_, None) => View::Braille,
+                                };
                             }
-                            KeyCode::Left => match self.current_parameter {
-                                ParameterType::Temp => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.temp_param.decrease_value(*self.temp_param.step());
-                                    } else {
-                                        self.temp_param.decrease_value(self.temp_param.step() * 8);
-                                    }
-                                },
-                                ParameterType::Coupling => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.coupling_param.decrease_value(*self.coupling_param.step());
-                                    } else {
-                                        self.coupling_param.decrease_value(self.coupling_param.step() * 8);
-                                    }
-                                },
-                                ParameterType::MagMoment => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.mag_moment_param.decrease_value(*self.mag_moment_param.step());
-                                    } else {
-                                        self.mag_moment_param.decrease_value(self.mag_moment_param.step() * 8);
-                                    }
-                                },
-                                ParameterType::MagFieldStrength => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.mag_field_strength_param.decrease_value(*self.mag_field_strength_param.step());
-                                    } else {
-                                        self.mag_field_strength_param.decrease_value(self.mag_field_strength_param.step() * 8);
-                                    }
-                                },
+                            KeyCode::Char('[') | KeyCode::Char(']') => {
+                                let depth = self.ising.geometry.depth();
+                                self.slice = match key_event.code {
+                                    KeyCode::Char('[') => (self.slice + depth - 1) % depth,
+                                    _ => (self.slice + 1) % depth,
+                                };
                             }
-                            KeyCode::Right => match self.current_parameter {
-                                ParameterType::Temp => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.temp_param.increase_value(*self.temp_param.step());
-                                    } else {
-                                        self.temp_param.increase_value(self.temp_param.step() * 8);
-                                    }
-                                },
-                                ParameterType::Coupling => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.coupling_param.increase_value(*self.coupling_param.step());
-                                    } else {
-                                        self.coupling_param.increase_value(self.coupling_param.step() * 8);
-                                    }
-                                },
-                                ParameterType::MagMoment => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.mag_moment_param.increase_value(*self.mag_moment_param.step());
-                                    } else {
-                                        self.mag_moment_param.increase_value(self.mag_moment_param.step() * 8);
-                                    }
-                                },
-                                ParameterType::MagFieldStrength => {
-                                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                                        self.mag_field_strength_param.increase_value(*self.mag_field_strength_param.step());
-                                    } else {
-                                        self.mag_field_strength_param.increase_value(self.mag_field_strength_param.step() * 8);
-                                    }
-                                },
+                            KeyCode::Up | KeyCode::Down => {
+                                let parameters = self.parameters();
+                                let current = parameters
+                                    .iter()
+                                    .position(|&p| p == self.current_parameter)
+                                    .unwrap_or(0);
+                                let next = match key_event.code {
+                                    KeyCode::Up => current.saturating_sub(1),
+                                    _ => (current + 1).min(parameters.len() - 1),
+                                };
+                                self.current_parameter = parameters[next];
+                            }
+                            KeyCode::Left | KeyCode::Right => {
+                                let fine = key_event.modifiers.contains(KeyModifiers::SHIFT);
+                                let param = self.parameter_mut(self.current_parameter);
+                                let step = if fine { *param.step() } else { param.step() * 8 };
+                                if key_event.code == KeyCode::Left {
+                                    param.decrease_value(step);
+                                } else {
+                                    param.increase_value(step);
+                                }
                             }
                             _ => {}
                         }
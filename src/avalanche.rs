@@ -0,0 +1,62 @@
+use crate::ising::Ising;
+
+/// Number of logarithmic avalanche size bins, [2^k, 2^(k+1)) for k < SIZE_BINS.
+const SIZE_BINS: usize = 18;
+
+/// Adiabatic zero temperature hysteresis loop of the uniform field, recording the
+/// avalanches (Barkhausen noise) it triggers.
+#[derive(Debug)]
+pub struct FieldSweep {
+    /// +1 while the field increases, -1 while it decreases.
+    pub direction: i8,
+    pub last_size: usize,
+    pub size_counts: Vec<u64>,
+}
+
+impl FieldSweep {
+    /// Saturates `ising` with every spin down and the field below every flip threshold.
+    pub fn new(ising: &mut Ising) -> Self {
+        let max_field = (0..ising.lattice.len())
+            .map(|idx| {
+                let bonds: f64 = ising.bonds[idx]
+                    .iter()
+                    .map(|bond| (ising.coupling(bond.kind) * bond.weight).abs())
+                    .sum();
+                let walls: f64 = ising.walls[idx].iter().map(|wall| ising.coupling(wall.kind).abs()).sum();
+                bonds + walls + ising.fields[idx].abs()
+            })
+            .fold(0.0, f64::max);
+
+        for (s, &occupied) in ising.lattice.iter_mut().zip(ising.occupancy.iter()) {
+            if occupied {
+                *s = -1;
+            }
+        }
+        ising.magnetic_moment = 1.0;
+        ising.magnetic_field_strength = -max_field - 1.0;
+
+        Self {
+            direction: 1,
+            last_size: 0,
+            size_counts: vec![0; SIZE_BINS],
+        }
+    }
+
+    /// Triggers the next avalanche, reversing the sweep once the system saturates.
+    pub fn step(&mut self, ising: &mut Ising) -> usize {
+        let size = match ising.next_avalanche(self.direction) {
+            Some(size) => size,
+            None => {
+                self.direction = -self.direction;
+                ising.next_avalanche(self.direction).unwrap_or(0)
+            }
+        };
+
+        if size > 0 {
+            let bin = (usize::BITS - 1 - size.leading_zeros()) as usize;
+            self.size_counts[bin.min(SIZE_BINS - 1)] += 1;
+        }
+        self.last_size = size;
+        size
+    }
+}
@@ -0,0 +1,671 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::rc::Rc;
+
+use crate::anneal::Annealer;
+use crate::cluster::Algorithm;
+use crate::disorder::{self, Distribution};
+use crate::exact::Validation;
+use crate::geometry::Geometry;
+use crate::interface::Integration;
+use crate::ising::{Ising, Model};
+use crate::lattice::{Boundary, Grid, Lattice, Rectangular};
+use crate::multicanonical::{Coordinate, Multicanonical};
+use crate::network::Network;
+use crate::potts::Comparison;
+use crate::problem::Format;
+use crate::reweighting::Recording;
+use crate::schedule::Schedule;
+use crate::tempering::ParallelTempering;
+use crate::wang_landau::WangLandau;
+
+pub const USAGE: &str = "\
+Usage: ising [options]
+       ising solve <problem> [solver options]
+       ising interface [interface options]
+       ising potts [comparison options]
+       ising exact [exact options]
+       ising wang-landau [density of states options]
+       ising multicanonical [multicanonical options]
+       ising reweight [reweighting options]
+
+Options:
+  --size <n>           Lattice side length, a multiple of 4 (default 200, or 48 for cubic)
+  --geometry <name>    square (default), triangular, honeycomb, kagome or cubic (3D, drawn
+                       one slice at a time)
+  --height <n>         Height of a rectangular square lattice, a multiple of 4
+                       (default --size)
+  --boundary <x>[,<y>] Boundary conditions of the square geometry along x and y: periodic
+                       (default), open, antiperiodic, helical or fixed (up before the
+                       first row/column, down after the last)
+  --j2 <J>             Square geometry with second neighbour couplings across the diagonals,
+                       starting at J
+  --j3 <J>             Square geometry with axial third neighbour couplings two sites
+                       apart along x (ANNNI model), starting at J
+  --graph <spec>       Network to run on instead of a lattice:
+                         edges:path             'u v' lines, one per edge
+                         adjacency:path         'u v1 v2 ...' neighbour lists
+                         graphml:path           GraphML nodes and edges
+                         regular:n:k            random k-regular graph
+                         small-world:n:k:p      Watts-Strogatz ring, rewiring probability p
+                         scale-free:n:m         Barabási-Albert, m links per new site
+  --potts <q>          q-state Potts spins, 2 <= q <= 64, drawn in q colors
+  --blume-capel <D>    Spin-1 Blume-Capel model, σ in {-1, 0, +1}, with crystal field D σ²
+                       starting at D. On the square lattice the transition turns first
+                       order past the tricritical point D ≈ 1.966, T ≈ 0.608
+  --xy                 Planar rotors (XY model) on the square geometry, colored by angle
+  --clock <p>          p-state clock model, rotors at multiples of 2π/p, 2 <= p <= 64
+  --heisenberg         Classical Heisenberg model of unit 3-vector spins, on any geometry.
+                       It has no order at T > 0 in two dimensions (Mermin-Wagner)
+  --anisotropy <D>     Starting single-ion anisotropy -D (S^z)² of the Heisenberg model,
+                       towards the Ising model for D > 0 (default 0)
+  --update <name>      metropolis (default), wolff or swendsen-wang cluster updates
+  --schedule <spec>    Temperature schedule followed automatically, then held at its
+                       final temperature:
+                         quench:T:frames        random start, then hold T
+                         linear:T0:T1:frames    linear cooling/heating
+                         exp:T0:T1:frames       exponential cooling/heating
+                         file:path              piecewise 'frame temperature' lines
+  --tempering <spec>   T0:T1:r, parallel tempering of r replicas at geometrically spaced
+                       temperatures, shown side by side, with swap acceptance and
+                       round trips between the coldest and hottest
+  --wang-landau        Wang-Landau walk in energy instead of dynamics, with its histogram
+                       flattening and thermodynamics from g(E) at the set temperature,
+                       and the density of states options below
+  --output <path>      File (x) writes to: the (E, M) series recorded at every temperature
+                       and field held, as --series reads them (default series.txt), or
+                       g(E) with --wang-landau
+  --disorder <dist>    Edwards-Anderson spin glass with random couplings, bimodal (±J)
+                       or gaussian, and a second replica for the overlap P(q)
+  --dilution <p>       Fraction of sites left as non-magnetic vacancies
+  --random-field <dist>
+                       Random-field Ising model with quenched local fields, bimodal
+                       or gaussian, of adjustable strength
+  --seed <n>           Random seed for disorder and solvers
+  -h, --help           Print this message
+
+Solver:
+  <problem>            Problem file, read according to --format
+  --format <name>      ising: 'i j value' lines, couplings J_ij or fields h_i when i = j
+                       qubo:  'i j Q_ij' lines, minimizes x^T Q x over x in {0, 1}
+                       gset:  Gset max-cut graph, 'n m' then 1-based 'i j w' edges
+                       dimacs: max-cut graph, 'p edge n m' then 'e i j [w]' edges
+  --method <name>      anneal (default) or tempering
+  --sweeps <n>         Sweeps per run (default 1000)
+  --t-start <T>        Highest temperature (default 3.0)
+  --t-end <T>          Lowest temperature (default 0.05)
+  --restarts <n>       Independent annealing runs (default 4)
+  --replicas <n>       Parallel tempering replicas (default 16)
+
+Interface tension, square lattice of side --size (default 32):
+  --temperatures <spec>
+                       T0:T1:n for n evenly spaced temperatures (default 1.0:2.5:7)
+  --points <n>         Values of the seam coupling in [-1, 1] (default 21)
+  --sweeps <n>         Measurement sweeps per seam coupling (default 1000)
+
+Ising vs Potts transitions, square lattice of side --size (default 32):
+  --states <q>,...     Potts models run besides the Ising model (default 3,4,5,8)
+  --temperatures <spec>
+                       T0:T1:n for n evenly spaced T / T_c, heated through in order
+                       (default 0.9:1.1:11)
+  --sweeps <n>         Measurement sweeps per temperature (default 1000)
+  --update <name>      Update algorithm (default swendsen-wang)
+
+Exact solutions of Ising spins, beside Monte Carlo estimates:
+  --size <n>           Lattice enumerated, every configuration of up to 25 spins, with
+                       the lattice options above and any side of at least 2 (default 4)
+  --strip <w>          Transfer matrix of the infinite square lattice strip of w <= 12
+                       spins around instead
+  --field <h>          Uniform field h = μB (default 0)
+  --temperatures <spec>
+                       T0:T1:n for n evenly spaced temperatures (default 1.0:2.5:7)
+  --sweeps <n>         Measurement sweeps per temperature (default 1000)
+  --update <name>      Update algorithm (default metropolis)
+
+Wang-Landau density of states of discrete spins, with the lattice and model options
+above (default size 16):
+  --flatness <x>       Lowest over mean visits of a flat histogram (default 0.8)
+  --tolerance <ln f>   ln f at which refinement stops, halving from 1 (default 1e-6)
+  --bin-width <ΔE>     Energy bins, for continuous disorder (default one per level)
+  --output <path>      Writes 'E ln_g(E)' lines, with g(E) summing to the number of
+                       configurations
+  --temperatures <spec>
+                       T0:T1:n for n evenly spaced temperatures (default 1.0:2.5:7)
+
+Multicanonical sampling of discrete spins across free energy barriers, reweighted to
+canonical averages, with the lattice and model options above (default size 16):
+  --umbrella <x>       magnetization (default) or energy, the coordinate flattened. The
+                       magnetization barrier of an L x L torus gives the interface
+                       tension σ(L) = T ΔF / 2L
+  --iterations <n>     Halvings of the Wang-Landau factor adapting the weights W(x)
+                       (default 10)
+  --flatness <x>       Lowest over mean visits of a flat histogram (default 0.8)
+  --weights <path>     Starting weights, 'x ln_W(x)' lines with x the total energy or
+                       magnetization, adapted further unless --iterations 0
+  --output <path>      Writes 'x ln_W(x) ln_P(x)' lines, the weights and the canonical
+                       distribution
+  --bin-width <ΔE>     Energy bins, for continuous disorder (default one per level)
+  --sweeps <n>         Sweeps sampled with the final weights (default 50000)
+  --temperatures <spec>
+                       T0:T1:n for n evenly spaced temperatures, each adapted from the
+                       start (default 1.8:2.2:3)
+
+Ferrenberg-Swendsen reweighting of (E, M) series of Ising or Blume-Capel spins to
+nearby temperatures and fields, single- or multi-histogram, with the peaks of C and χ
+located, and the lattice and model options above (default size 32):
+  --series <path>      '# sites N' then 'T h E M' lines, as (x) writes them in the
+                       interactive view, reweighted instead of runs recorded here
+  --runs <spec>        T0:T1:n for n evenly spaced temperatures recorded in zero field,
+                       heated through in order (default 2.3:2.3:1)
+  --sweeps <n>         Sweeps recorded per temperature, one sample each (default 20000)
+  --update <name>      Update algorithm (default swendsen-wang)
+  --output <path>      Writes the series recorded, as --series reads them
+  --field <h>          Uniform field h = μB reweighted to (default 0)
+  --temperatures <spec>
+                       T0:T1:n for n evenly spaced temperatures reweighted to (default 13
+                       across the range the series reach)
+";
+
+#[derive(Debug)]
+pub enum Command {
+    Interactive,
+    Solve(String),
+    /// Interface tension from periodic to antiperiodic thermodynamic integration.
+    Interface,
+    /// Ising and Potts transitions side by side.
+    Potts,
+    /// Exact thermodynamics of small systems against Monte Carlo.
+    Exact,
+    /// Thermodynamics from a Wang-Landau density of states.
+    WangLandau,
+    /// Free energy barriers from multicanonical sampling.
+    Multicanonical,
+    /// Histogram reweighting of recorded series.
+    Reweight,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Method {
+    Anneal,
+    Tempering,
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub command: Command,
+    pub size: usize,
+    pub geometry: Geometry,
+    pub height: Option<usize>,
+    /// Along x and y.
+    pub boundaries: [Boundary; 2],
+    pub diagonal_coupling: Option<f64>,
+    pub axial_coupling: Option<f64>,
+    pub network: Option<Network>,
+    /// Number of Potts states, for Potts instead of Ising spins.
+    pub potts: Option<i8>,
+    /// Initial crystal field, for Blume-Capel instead of Ising spins.
+    pub crystal_field: Option<f64>,
+    pub xy: bool,
+    pub heisenberg: bool,
+    pub anisotropy: f64,
+    /// Number of clock states, for clock model rotors.
+    pub clock: Option<usize>,
+    pub algorithm: Algorithm,
+    pub schedule: Option<Schedule>,
+    /// Temperature ladder of interactive parallel tempering.
+    pub tempering: Option<Vec<f64>>,
+    /// Wang-Landau walk shown in the interactive view.
+    pub density_of_states: bool,
+    pub dilution: f64,
+    pub disorder: Option<Distribution>,
+    pub random_field: Option<Distribution>,
+    pub format: Format,
+    pub method: Method,
+    pub annealer: Annealer,
+    pub replicas: usize,
+    pub integration: Integration,
+    pub comparison: Comparison,
+    pub validation: Validation,
+    /// Width of the strip solved by transfer matrix.
+    pub strip: Option<usize>,
+    /// Uniform field of exact solutions and reweighting.
+    pub field: f64,
+    pub wang_landau: WangLandau,
+    pub multicanonical: Multicanonical,
+    /// Starting multicanonical weights.
+    pub weights: Option<String>,
+    /// File the density of states, multicanonical weights or recorded series are
+    /// written to.
+    pub output: Option<String>,
+    pub recording: Recording,
+    /// Temperatures recorded for reweighting.
+    pub runs: Vec<f64>,
+    /// Recorded series reweighted instead.
+    pub series: Option<String>,
+    pub temperatures: Vec<f64>,
+    pub seed: Option<u64>,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Self {
+            command: Command::Interactive,
+            size: 200,
+            geometry: Geometry::Square,
+            height: None,
+            boundaries: [Boundary::Periodic; 2],
+            diagonal_coupling: None,
+            axial_coupling: None,
+            network: None,
+            potts: None,
+            crystal_field: None,
+            xy: false,
+            heisenberg: false,
+            anisotropy: 0.0,
+            clock: None,
+            algorithm: Algorithm::Metropolis,
+            schedule: None,
+            tempering: None,
+            density_of_states: false,
+            dilution: 0.0,
+            disorder: None,
+            random_field: None,
+            format: Format::Ising,
+            method: Method::Anneal,
+            annealer: Annealer::default(),
+            replicas: 16,
+            integration: Integration::default(),
+            comparison: Comparison::default(),
+            validation: Validation::default(),
+            strip: None,
+            field: 0.0,
+            wang_landau: WangLandau::default(),
+            multicanonical: Multicanonical::default(),
+            weights: None,
+            output: None,
+            recording: Recording::default(),
+            runs: vec![2.3],
+            series: None,
+            temperatures: Vec::new(),
+            seed: None,
+            help: false,
+        };
+        let mut size = None;
+        let mut temperatures = None;
+        let mut positional = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--size" => size = Some(parse_value(&mut args, &arg)?),
+                "--height" => options.height = Some(parse_value(&mut args, &arg)?),
+                "--boundary" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let (x, y) = value.split_once(',').unwrap_or((&value, &value));
+                    options.boundaries = [Boundary::parse(x)?, Boundary::parse(y)?];
+                }
+                "--j2" => options.diagonal_coupling = Some(parse_value(&mut args, &arg)?),
+                "--j3" => options.axial_coupling = Some(parse_value(&mut args, &arg)?),
+                "--graph" => options.network = Some(Network::parse(&next_value(&mut args, &arg)?)?),
+                "--potts" => {
+                    let states: i8 = parse_value(&mut args, &arg)?;
+                    if !(2..=64).contains(&states) {
+                        return Err(format!("--potts must be between 2 and 64, got {}", states));
+                    }
+                    options.potts = Some(states);
+                }
+                "--blume-capel" => options.crystal_field = Some(parse_value(&mut args, &arg)?),
+                "--xy" => options.xy = true,
+                "--heisenberg" => options.heisenberg = true,
+                "--anisotropy" => options.anisotropy = parse_value(&mut args, &arg)?,
+                "--clock" => {
+                    let states: usize = parse_value(&mut args, &arg)?;
+                    if !(2..=64).contains(&states) {
+                        return Err(format!("--clock must be between 2 and 64, got {}", states));
+                    }
+                    options.clock = Some(states);
+                }
+                "--update" => {
+                    options.algorithm = Algorithm::parse(&next_value(&mut args, &arg)?)?;
+                    options.comparison.algorithm = options.algorithm;
+                    options.validation.algorithm = options.algorithm;
+                    options.recording.algorithm = options.algorithm;
+                }
+                "--states" => {
+                    options.comparison.states = next_value(&mut args, &arg)?
+                        .split(',')
+                        .map(|q| match q.parse::<i8>() {
+                            Ok(states) if (2..=64).contains(&states) => Ok(states),
+                            _ => Err(format!("invalid number of Potts states '{}'", q)),
+                        })
+                        .collect::<Result<Vec<i8>, String>>()?;
+                }
+                "--geometry" => options.geometry = Geometry::parse(&next_value(&mut args, &arg)?)?,
+                "--schedule" => {
+                    options.schedule = Some(Schedule::parse(&next_value(&mut args, &arg)?)?);
+                }
+                "--tempering" => options.tempering = Some(parse_ladder(&next_value(&mut args, &arg)?)?),
+                "--wang-landau" => options.density_of_states = true,
+                "--flatness" => {
+                    options.wang_landau.flatness = parse_value(&mut args, &arg)?;
+                    if !(0.0..1.0).contains(&options.wang_landau.flatness) {
+                        return Err(format!("--flatness must be in [0, 1), got {}", options.wang_landau.flatness));
+                    }
+                    options.multicanonical.flatness = options.wang_landau.flatness;
+                }
+                "--tolerance" => {
+                    options.wang_landau.tolerance = parse_value(&mut args, &arg)?;
+                    if options.wang_landau.tolerance <= 0.0 {
+                        return Err(format!("--tolerance must be positive, got {}", options.wang_landau.tolerance));
+                    }
+                }
+                "--bin-width" => {
+                    options.wang_landau.bin_width = parse_value(&mut args, &arg)?;
+                    if options.wang_landau.bin_width < 0.0 {
+                        return Err(format!("--bin-width cannot be negative, got {}", options.wang_landau.bin_width));
+                    }
+                    options.multicanonical.bin_width = options.wang_landau.bin_width;
+                }
+                "--output" => options.output = Some(next_value(&mut args, &arg)?),
+                "--umbrella" => options.multicanonical.coordinate = Coordinate::parse(&next_value(&mut args, &arg)?)?,
+                "--iterations" => options.multicanonical.iterations = parse_value(&mut args, &arg)?,
+                "--weights" => options.weights = Some(next_value(&mut args, &arg)?),
+                "--series" => options.series = Some(next_value(&mut args, &arg)?),
+                "--runs" => {
+                    options.runs = parse_range(&next_value(&mut args, &arg)?)?;
+                    if options.runs.iter().any(|&temperature| temperature <= 0.0) {
+                        return Err("--runs temperatures must be positive".to_string());
+                    }
+                }
+                "--dilution" => {
+                    options.dilution = parse_value(&mut args, &arg)?;
+                    if !(0.0..1.0).contains(&options.dilution) {
+                        return Err(format!("dilution must be in [0, 1), got {}", options.dilution));
+                    }
+                }
+                "--disorder" => options.disorder = Some(Distribution::parse(&next_value(&mut args, &arg)?)?),
+                "--random-field" => {
+                    options.random_field = Some(Distribution::parse(&next_value(&mut args, &arg)?)?)
+                }
+                "--format" => options.format = Format::parse(&next_value(&mut args, &arg)?)?,
+                "--method" => {
+                    options.method = match next_value(&mut args, &arg)?.as_str() {
+                        "anneal" => Method::Anneal,
+                        "tempering" => Method::Tempering,
+                        other => return Err(format!("unrecognized method '{}'", other)),
+                    }
+                }
+                "--replicas" => options.replicas = parse_value(&mut args, &arg)?,
+                "--sweeps" => {
+                    options.annealer.sweeps = parse_value(&mut args, &arg)?;
+                    options.integration.sweeps = options.annealer.sweeps;
+                    options.comparison.sweeps = options.annealer.sweeps;
+                    options.validation.sweeps = options.annealer.sweeps;
+                    options.multicanonical.sweeps = options.annealer.sweeps;
+                    options.recording.sweeps = options.annealer.sweeps;
+                }
+                "--strip" => options.strip = Some(parse_value(&mut args, &arg)?),
+                "--field" => options.field = parse_value(&mut args, &arg)?,
+                "--points" => options.integration.points = parse_value(&mut args, &arg)?,
+                "--temperatures" => temperatures = Some(parse_range(&next_value(&mut args, &arg)?)?),
+                "--t-start" => options.annealer.t_start = parse_value(&mut args, &arg)?,
+                "--t-end" => options.annealer.t_end = parse_value(&mut args, &arg)?,
+                "--restarts" => options.annealer.restarts = parse_value(&mut args, &arg)?,
+                "--seed" => options.seed = Some(parse_value(&mut args, &arg)?),
+                "-h" | "--help" => options.help = true,
+                _ if !arg.starts_with('-') => positional.push(arg),
+                _ => return Err(format!("unrecognized argument '{}'\n\n{}", arg, USAGE)),
+            }
+        }
+
+        options.command = match positional.as_slice() {
+            [] => Command::Interactive,
+            [command, path] if command == "solve" => Command::Solve(path.clone()),
+            [command] if command == "interface" => Command::Interface,
+            [command] if command == "potts" => Command::Potts,
+            [command] if command == "exact" => Command::Exact,
+            [command] if command == "wang-landau" => Command::WangLandau,
+            [command] if command == "multicanonical" => Command::Multicanonical,
+            [command] if command == "reweight" => Command::Reweight,
+            _ => return Err(format!("unexpected arguments '{}'\n\n{}", positional.join(" "), USAGE)),
+        };
+
+        // L^3 sites with the 2D default would not fit in memory
+        let default_size = match options.command {
+            Command::Interface | Command::Potts | Command::Reweight => 32,
+            Command::Exact => 4,
+            Command::WangLandau | Command::Multicanonical => 16,
+            _ if options.geometry == Geometry::Cubic => 48,
+            _ => 200,
+        };
+        options.size = size.unwrap_or(default_size);
+        for (flag, side) in [("--size", Some(options.size)), ("--height", options.height)] {
+            if let Some(side) = side {
+                check_side(flag, side, &options)?;
+            }
+        }
+        options.temperatures = temperatures.unwrap_or_else(|| match options.command {
+            Command::Potts => linspace(0.9, 1.1, 11),
+            Command::Multicanonical => linspace(1.8, 2.2, 3),
+            // Spread over the range the series reach
+            Command::Reweight => Vec::new(),
+            _ => linspace(1.0, 2.5, 7),
+        });
+        // The annealing and tempering ladders are geometric
+        if options.annealer.t_start <= 0.0 || options.annealer.t_end <= 0.0 {
+            return Err("--t-start and --t-end must be positive".to_string());
+        }
+        if options.replicas < 2 {
+            return Err(format!("--replicas must be at least 2, got {}", options.replicas));
+        }
+        let rectangular = options.geometry == Geometry::Square && options.network.is_none();
+        if options.height.is_some() && !rectangular {
+            return Err("--height requires the square geometry".to_string());
+        }
+        if options.boundaries != [Boundary::Periodic; 2] && !rectangular {
+            return Err("--boundary requires the square geometry".to_string());
+        }
+        if (options.diagonal_coupling.is_some() || options.axial_coupling.is_some()) && !rectangular {
+            return Err("--j2 and --j3 require the square geometry".to_string());
+        }
+        if options.potts.is_some() && options.disorder.is_some() {
+            return Err("--potts cannot be combined with --disorder".to_string());
+        }
+        if options.potts.is_some() && options.crystal_field.is_some() {
+            return Err("--potts and --blume-capel are different models".to_string());
+        }
+        if options.heisenberg {
+            let discrete = options.potts.is_some() || options.crystal_field.is_some();
+            if discrete || options.is_planar() || options.disorder.is_some() {
+                return Err("--heisenberg cannot be combined with other models or --disorder".to_string());
+            }
+        } else if options.anisotropy != 0.0 {
+            return Err("--anisotropy requires --heisenberg".to_string());
+        }
+        if matches!(options.command, Command::Potts) && options.crystal_field.is_some() {
+            return Err("the potts command compares the Ising and Potts models only".to_string());
+        }
+        if let Command::Exact = options.command {
+            let other = options.potts.is_some() || options.crystal_field.is_some();
+            if other || options.is_planar() || options.heisenberg {
+                return Err("exact solutions are of Ising spins".to_string());
+            }
+        } else if options.strip.is_some() {
+            return Err("--strip is an option of the exact command".to_string());
+        }
+        if options.tempering.is_some() {
+            if options.is_planar() || options.heisenberg {
+                return Err("--tempering requires discrete spins".to_string());
+            }
+            if options.schedule.is_some() {
+                return Err("--tempering holds its temperatures and cannot follow a --schedule".to_string());
+            }
+        }
+        let wang_landau = options.density_of_states || matches!(options.command, Command::WangLandau);
+        if wang_landau {
+            if options.is_planar() || options.heisenberg {
+                return Err("the Wang-Landau walk requires discrete spins".to_string());
+            }
+            if options.density_of_states && (options.schedule.is_some() || options.tempering.is_some()) {
+                return Err("--wang-landau replaces the dynamics and cannot follow a --schedule or --tempering".to_string());
+            }
+        } else if let Command::Multicanonical = options.command {
+            if options.is_planar() || options.heisenberg {
+                return Err("multicanonical sampling requires discrete spins".to_string());
+            }
+            if options.potts.is_some() && options.multicanonical.coordinate == Coordinate::Magnetization {
+                return Err("Potts spins have no magnetization to flatten, use --umbrella energy".to_string());
+            }
+            if (options.weights.is_some() || options.output.is_some()) && options.temperatures.len() > 1 {
+                return Err("--weights and --output hold the weights of a single temperature".to_string());
+            }
+        } else if let Command::Reweight = options.command {
+            let discrete = options.potts.is_none() && !options.is_planar() && !options.heisenberg;
+            if !discrete {
+                return Err("histogram reweighting requires Ising or Blume-Capel spins".to_string());
+            }
+            if options.series.is_some() && options.output.is_some() {
+                return Err("--output writes recorded series, and --series reads them instead".to_string());
+            }
+        } else if options.output.is_some() && !matches!(options.command, Command::Interactive) {
+            return Err("--output writes the Wang-Landau density of states, multicanonical weights or recorded series".to_string());
+        }
+        if options.series.is_some() && !matches!(options.command, Command::Reweight) {
+            return Err("--series is an option of the reweight command".to_string());
+        }
+        if options.weights.is_some() && !matches!(options.command, Command::Multicanonical) {
+            return Err("--weights is an option of the multicanonical command".to_string());
+        }
+        if options.is_planar() {
+            if options.xy && options.clock.is_some() {
+                return Err("--xy and --clock are different models".to_string());
+            }
+            if !rectangular {
+                return Err("--xy and --clock require the square geometry".to_string());
+            }
+            if options.potts.is_some() || options.crystal_field.is_some() || options.disorder.is_some() {
+                return Err("--xy and --clock cannot be combined with --potts, --blume-capel or --disorder".to_string());
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Whether the spins are planar rotors rather than discrete.
+    pub fn is_planar(&self) -> bool {
+        self.xy || self.clock.is_some()
+    }
+
+    /// Network given by --graph, or else the lattice described by --geometry, --size and
+    /// --height.
+    pub fn lattice<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Rc<dyn Lattice>, String> {
+        if let Some(network) = &self.network {
+            return network.build(rng);
+        }
+        Ok(match self.geometry {
+            Geometry::Square => Rc::new(Rectangular {
+                height: self.height.unwrap_or(self.size),
+                boundaries: self.boundaries,
+                diagonal: self.diagonal_coupling.is_some(),
+                axial: self.axial_coupling.is_some(),
+                ..Rectangular::square(self.size)
+            }),
+            geometry => Rc::new(Grid {
+                geometry,
+                size: self.size,
+            }),
+        })
+    }
+
+    /// Spins on the lattice above, with the model, further couplings, dilution and bond
+    /// disorder the options give, as the subcommands simulate them.
+    pub fn ising<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Ising, String> {
+        let mut ising = Ising::new(self.lattice(rng)?, rng);
+        if let Some(states) = self.potts {
+            ising.model = Model::Potts { states };
+        }
+        if let Some(crystal_field) = self.crystal_field {
+            ising.model = Model::BlumeCapel;
+            ising.crystal_field = crystal_field;
+        }
+        ising.diagonal_coupling = self.diagonal_coupling.unwrap_or(0.0);
+        ising.axial_coupling = self.axial_coupling.unwrap_or(0.0);
+        if self.dilution > 0.0 {
+            ising.dilute(self.dilution, rng);
+        }
+        if let Some(distribution) = self.disorder {
+            disorder::randomize_bonds(&mut ising, distribution, rng);
+        }
+        Ok(ising)
+    }
+
+    /// Generator seeded with `--seed`, or from entropy without one.
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} expects a value", flag))
+}
+
+fn parse_value<T: std::str::FromStr, I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<T, String> {
+    let value = next_value(args, flag)?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+/// Lattice side length, which the braille view needs to be a multiple of 4. Enumeration
+/// draws nothing, and only needs the honeycomb and kagome grids to wrap evenly.
+fn check_side(flag: &str, value: usize, options: &Options) -> Result<(), String> {
+    if let Command::Exact = options.command {
+        let even = matches!(options.geometry, Geometry::Honeycomb | Geometry::Kagome);
+        if value < 2 || (even && !value.is_multiple_of(2)) {
+            let least = if even { "an even number of at least 2" } else { "at least 2" };
+            return Err(format!("{} must be {} for the exact command, got {}", flag, least, value));
+        }
+    } else if value == 0 || !value.is_multiple_of(4) {
+        return Err(format!("{} must be a positive multiple of 4, got {}", flag, value));
+    }
+    Ok(())
+}
+
+/// Parses `T0:T1:n` into n evenly spaced values from T0 to T1.
+fn parse_range(spec: &str) -> Result<Vec<f64>, String> {
+    let fields: Vec<&str> = spec.split(':').collect();
+    let range = match fields.as_slice() {
+        [start, end, count] => start.parse().ok().zip(end.parse().ok()).zip(count.parse().ok()),
+        _ => None,
+    };
+    match range {
+        Some(((start, end), count)) if count > 0 => Ok(linspace(start, end, count)),
+        _ => Err(format!("expected 'T0:T1:n', got '{}'", spec)),
+    }
+}
+
+/// Parses `T0:T1:r` into a geometric ladder of r temperatures from T0 to T1.
+fn parse_ladder(spec: &str) -> Result<Vec<f64>, String> {
+    let fields: Vec<&str> = spec.split(':').collect();
+    let ladder = match fields.as_slice() {
+        [start, end, count] => start.parse::<f64>().ok().zip(end.parse::<f64>().ok()).zip(count.parse::<usize>().ok()),
+        _ => None,
+    };
+    match ladder {
+        Some(((start, end), count)) if 0.0 < start && start < end && (2..=64).contains(&count) => {
+            Ok(ParallelTempering::geometric_ladder(start, end, count))
+        }
+        _ => Err(format!("expected 'T0:T1:r' with 0 < T0 < T1 and 2 <= r <= 64, got '{}'", spec)),
+    }
+}
+
+fn linspace(start: f64, end: f64, count: usize) -> Vec<f64> {
+    if count == 1 {
+        return vec![start];
+    }
+    (0..count)
+        .map(|i| start + (end - start) * i as f64 / (count - 1) as f64)
+        .collect()
+}
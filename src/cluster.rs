@@ -0,0 +1,226 @@
+use rand::Rng;
+use std::fmt;
+
+use crate::ising::{Bond, Ising, Model};
+
+/// How spins are updated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    /// Single spin Metropolis steps.
+    Metropolis,
+    /// One Fortuin-Kasteleyn cluster grown from a random site at a time.
+    Wolff,
+    /// Every Fortuin-Kasteleyn cluster of the lattice at once.
+    SwendsenWang,
+}
+
+impl Algorithm {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "metropolis" => Ok(Algorithm::Metropolis),
+            "wolff" => Ok(Algorithm::Wolff),
+            "swendsen-wang" => Ok(Algorithm::SwendsenWang),
+            _ => Err(format!("unrecognized update '{}'", name)),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Algorithm::Metropolis => Algorithm::Wolff,
+            Algorithm::Wolff => Algorithm::SwendsenWang,
+            Algorithm::SwendsenWang => Algorithm::Metropolis,
+        }
+    }
+
+    /// Spends about as much work as `steps` single spin updates: that many Metropolis
+    /// steps, one Wolff cluster per √N of them, or as many Swendsen-Wang sweeps as that
+    /// makes lattice sweeps. Cluster updates run at least once. The number of clusters is
+    /// fixed in advance, since stopping once enough sites have moved would favour the
+    /// states left behind by large clusters. Clusters never contain Blume-Capel zeros, so
+    /// they are followed by `steps` Metropolis steps to move those.
+    pub fn run<R: Rng + ?Sized>(&self, ising: &mut Ising, steps: usize, rng: &mut R) {
+        let num_sites = ising.lattice.len();
+        match self {
+            Algorithm::Metropolis => {
+                for _ in 0..steps {
+                    ising.monte_carlo_step(rng);
+                }
+            }
+            Algorithm::Wolff => {
+                for _ in 0..(steps as f64 / (num_sites as f64).sqrt()).max(1.0) as usize {
+                    wolff_step(ising, rng);
+                }
+            }
+            Algorithm::SwendsenWang => {
+                for _ in 0..(steps / num_sites).max(1) {
+                    swendsen_wang_step(ising, rng);
+                }
+            }
+        }
+        if *self != Algorithm::Metropolis && ising.model == Model::BlumeCapel {
+            Algorithm::Metropolis.run(ising, steps, rng);
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::Metropolis => write!(f, "Metropolis"),
+            Algorithm::Wolff => write!(f, "Wolff"),
+            Algorithm::SwendsenWang => write!(f, "Swendsen-Wang"),
+        }
+    }
+}
+
+/// Probability of putting a bond of site `i` into a cluster. A satisfied
+/// Ising bond costs 2|J| to break and a satisfied ferromagnetic Potts bond J, so it is
+/// activated with probability 1 - exp(-cost / T). Antiferromagnetic Potts bonds cannot be
+/// satisfied by sites in the same cluster, and are left to the acceptance step instead.
+fn activation(ising: &Ising, i: usize, bond: &Bond) -> f64 {
+    let strength = ising.coupling(bond.kind) * bond.weight * ising.pair(ising.lattice[i], ising.lattice[bond.site]);
+    if strength <= 0.0 {
+        return 0.0;
+    }
+    let cost = match ising.model {
+        Model::Ising | Model::BlumeCapel => 2.0 * strength,
+        Model::Potts { .. } => strength,
+    };
+    1.0 - (-cost / ising.temperature).exp()
+}
+
+/// Energy not accounted for by the cluster bonds when every site of `cluster` takes the
+/// spin `new(old)`: the fields, and the antiferromagnetic Potts bonds to sites outside.
+fn residual_energy<F: Fn(i8) -> i8>(ising: &Ising, cluster: &[usize], member: &[bool], new: F) -> f64 {
+    cluster
+        .iter()
+        .map(|&i| {
+            let spin = new(ising.lattice[i]);
+            let outside: f64 = match ising.model {
+                Model::Ising | Model::BlumeCapel => 0.0,
+                Model::Potts { .. } => ising.bonds[i]
+                    .iter()
+                    .filter(|bond| !member[bond.site] && ising.coupling(bond.kind) * bond.weight < 0.0)
+                    .map(|bond| -ising.coupling(bond.kind) * bond.weight * ising.pair(spin, ising.lattice[bond.site]))
+                    .sum(),
+            };
+            ising.field_energy(i, spin) + outside
+        })
+        .sum()
+}
+
+/// Grows a cluster from a random site and moves it as a whole: Ising and Blume-Capel spins
+/// are flipped, and Potts spins all take another state drawn uniformly. The move is accepted with the
+/// Metropolis probability of the residual energy. Returns the size of the cluster, or 0
+/// when the move is rejected.
+pub fn wolff_step<R: Rng + ?Sized>(ising: &mut Ising, rng: &mut R) -> usize {
+    let seed = rng.gen_range(0..ising.lattice.len());
+    if !ising.occupancy[seed] {
+        return 0;
+    }
+
+    let mut member = vec![false; ising.lattice.len()];
+    member[seed] = true;
+    let mut cluster = vec![seed];
+    let mut stack = vec![seed];
+    while let Some(i) = stack.pop() {
+        for bond in ising.bonds[i].iter() {
+            let j = bond.site;
+            if !member[j] && ising.occupancy[j] && rng.gen::<f64>() < activation(ising, i, bond) {
+                member[j] = true;
+                cluster.push(j);
+                stack.push(j);
+            }
+        }
+    }
+
+    let model = ising.model;
+    let new = ising.propose(ising.lattice[seed], rng);
+    let move_spin = |spin: i8| match model {
+        Model::Ising | Model::BlumeCapel => -spin,
+        Model::Potts { .. } => new,
+    };
+    let energy_diff = residual_energy(ising, &cluster, &member, move_spin) - residual_energy(ising, &cluster, &member, |s| s);
+    if energy_diff > 0.0 && rng.gen::<f64>() >= (-energy_diff / ising.temperature).exp() {
+        return 0;
+    }
+    for &i in cluster.iter() {
+        ising.lattice[i] = move_spin(ising.lattice[i]);
+    }
+    cluster.len()
+}
+
+/// Splits the whole lattice into clusters and gives each a new spin by heat bath over its
+/// residual energy: flipped or not for Ising and Blume-Capel spins, or any of the q states
+/// for Potts spins. Clusters are visited in turn, since antiferromagnetic Potts bonds
+/// couple them.
+pub fn swendsen_wang_step<R: Rng + ?Sized>(ising: &mut Ising, rng: &mut R) {
+    let num_sites = ising.lattice.len();
+    let mut parent: Vec<usize> = (0..num_sites).collect();
+    for i in 0..num_sites {
+        if !ising.occupancy[i] {
+            continue;
+        }
+        for bond in ising.bonds[i].iter() {
+            let j = bond.site;
+            if j > i && ising.occupancy[j] && rng.gen::<f64>() < activation(ising, i, bond) {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = vec![Vec::new(); num_sites];
+    for i in (0..num_sites).filter(|&i| ising.occupancy[i]) {
+        let r = root(&mut parent, i);
+        clusters[r].push(i);
+    }
+
+    // Ising clusters are multiplied by ±1, Potts clusters set to a state
+    let model = ising.model;
+    let options: Vec<i8> = match model {
+        Model::Ising | Model::BlumeCapel => vec![1, -1],
+        Model::Potts { states } => (1..=states).collect(),
+    };
+    let apply = |option: i8, spin: i8| match model {
+        Model::Ising | Model::BlumeCapel => option * spin,
+        Model::Potts { .. } => option,
+    };
+
+    let mut member = vec![false; num_sites];
+    for cluster in clusters.iter().filter(|cluster| !cluster.is_empty()) {
+        cluster.iter().for_each(|&i| member[i] = true);
+        let energies: Vec<f64> = options
+            .iter()
+            .map(|&option| residual_energy(ising, cluster, &member, |s| apply(option, s)))
+            .collect();
+        let lowest = energies.iter().copied().fold(f64::INFINITY, f64::min);
+        // Written so that T = 0 picks among the lowest options without dividing 0 by 0
+        let weights: Vec<f64> = energies
+            .iter()
+            .map(|&e| if e <= lowest { 1.0 } else { (-(e - lowest) / ising.temperature).exp() })
+            .collect();
+        let mut draw = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        let mut chosen = weights.len() - 1;
+        for (k, &weight) in weights.iter().enumerate() {
+            if draw < weight {
+                chosen = k;
+                break;
+            }
+            draw -= weight;
+        }
+        for &i in cluster.iter() {
+            ising.lattice[i] = apply(options[chosen], ising.lattice[i]);
+        }
+        cluster.iter().for_each(|&i| member[i] = false);
+    }
+}
+
+/// Representative of the set containing `i`, halving paths on the way.
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
@@ -0,0 +1,138 @@
+use rand::Rng;
+use std::f64::consts::PI;
+
+use crate::ising::{Bond, Ising};
+
+/// Distribution of quenched random couplings or fields, with unit width.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// ±1 with equal probability
+    Bimodal,
+    /// standard normal
+    Gaussian,
+}
+
+impl Distribution {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "bimodal" => Ok(Distribution::Bimodal),
+            "gaussian" => Ok(Distribution::Gaussian),
+            _ => Err(format!("unrecognized distribution '{}'", name)),
+        }
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self {
+            Distribution::Bimodal => {
+                if rng.gen::<bool>() {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            // Box-Muller transform
+            Distribution::Gaussian => {
+                let u: f64 = 1.0 - rng.gen::<f64>();
+                let v: f64 = rng.gen();
+                (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+            }
+        }
+    }
+}
+
+/// Gives every bond an independent weight drawn from `distribution`, the same from both
+/// of its ends (Edwards-Anderson disorder), times its weight on the lattice, which keeps
+/// the sign of an antiperiodic seam. Where a pair of sites has several bonds of a kind, as
+/// along the axis of an L = 4 torus with third neighbours, the k-th from one end gets the
+/// draw of the k-th from the other.
+pub fn randomize_bonds<R: Rng + ?Sized>(ising: &mut Ising, distribution: Distribution, rng: &mut R) {
+    for i in 0..ising.bonds.len() {
+        let lattice = ising.geometry.neighbors(i);
+        for (k, lattice_bond) in lattice.iter().enumerate() {
+            let Bond { site: j, kind, .. } = ising.bonds[i][k];
+            if j <= i {
+                continue;
+            }
+            let weight = distribution.sample(rng) * lattice_bond.weight;
+            ising.bonds[i][k].weight = weight;
+            let occurrence = ising.bonds[i][..k].iter().filter(|bond| bond.site == j && bond.kind == kind).count();
+            let reverse = ising.bonds[j].iter_mut().filter(|bond| bond.site == i && bond.kind == kind).nth(occurrence);
+            if let Some(reverse) = reverse {
+                reverse.weight = weight;
+            }
+        }
+    }
+}
+
+/// Quenched random local fields h_i = Δ r_i with unit draws r_i, so the strength Δ can
+/// be changed without redrawing the disorder.
+#[derive(Debug)]
+pub struct RandomField {
+    pub distribution: Distribution,
+    draws: Vec<f64>,
+}
+
+impl RandomField {
+    pub fn new<R: Rng + ?Sized>(distribution: Distribution, num_sites: usize, rng: &mut R) -> Self {
+        let mut field = Self {
+            distribution,
+            draws: vec![0.0; num_sites],
+        };
+        field.reseed(rng);
+        field
+    }
+
+    pub fn reseed<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let distribution = self.distribution;
+        self.draws.iter_mut().for_each(|r| *r = distribution.sample(rng));
+    }
+
+    pub fn apply(&self, ising: &mut Ising, strength: f64) {
+        for (h, r) in ising.fields.iter_mut().zip(self.draws.iter()) {
+            *h = strength * r;
+        }
+    }
+}
+
+/// Spin overlap q = Σ s_i^a s_i^b / N between two replicas, over the N occupied sites.
+/// Blume-Capel spins in the zero state count as occupied.
+pub fn overlap(a: &Ising, b: &Ising) -> f64 {
+    let sum: i64 = a.lattice.iter().zip(b.lattice.iter()).map(|(&x, &y)| (x * y) as i64).sum();
+    sum as f64 / a.num_occupied() as f64
+}
+
+/// Histogram of samples in [-1, 1], e.g. the overlap distribution P(q).
+#[derive(Debug)]
+pub struct Histogram {
+    pub counts: Vec<u64>,
+    pub total: u64,
+}
+
+impl Histogram {
+    pub fn new(bins: usize) -> Self {
+        Self {
+            counts: vec![0; bins],
+            total: 0,
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        let bins = self.counts.len();
+        let bin = ((value + 1.0) / 2.0 * bins as f64) as usize;
+        self.counts[bin.min(bins - 1)] += 1;
+        self.total += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.total = 0;
+    }
+
+    /// Bin contents as percentages of all samples.
+    pub fn percentages(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .map(|&count| (100 * count).checked_div(self.total).unwrap_or(0))
+            .collect()
+    }
+}
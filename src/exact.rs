@@ -0,0 +1,467 @@
+use rand::Rng;
+use std::collections::HashMap;
+
+use crate::cluster::Algorithm;
+use crate::ising::{Ising, Model};
+
+/// Largest number of spins enumerated, 2^25 configurations.
+pub const MAX_ENUMERATION_SITES: usize = 25;
+/// Widest strip solved by transfer matrix, a 2^12 x 2^12 matrix.
+pub const MAX_STRIP_WIDTH: usize = 12;
+
+/// Equilibrium observables per site at a temperature and uniform field h = μB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thermodynamics {
+    pub free_energy: f64,
+    /// ⟨E⟩ / N, field term included.
+    pub energy: f64,
+    /// ⟨M⟩ / N.
+    pub magnetization: f64,
+    /// ⟨|M|⟩ / N, the order parameter of a finite system, where ⟨M⟩ vanishes without a
+    /// field. Unknown for transfer matrices.
+    pub absolute_magnetization: Option<f64>,
+    /// (⟨E²⟩ - ⟨E⟩²) / (N T²).
+    pub specific_heat: f64,
+    /// (⟨M²⟩ - ⟨|M|⟩²) / (N T), the usual finite size estimate, which stays finite below
+    /// T_c where spins flip together too rarely to sample ⟨M⟩ = 0. The response
+    /// ∂⟨M⟩/∂h / N for transfer matrices.
+    pub susceptibility: f64,
+}
+
+/// Every configuration of a small Ising system, tallied by interaction energy (bonds,
+/// local and wall fields) and magnetization, which gives its exact thermodynamics at any
+/// temperature and uniform field. Configurations are visited in Gray code order, so each
+/// differs from the last by a single flip.
+#[derive(Debug)]
+pub struct Enumeration {
+    sites: usize,
+    /// Density of states: (interaction energy, M, number of configurations).
+    states: Vec<(f64, i32, f64)>,
+    /// Couplings, fields and occupancy enumerated with, to tell when they have changed.
+    conditions: Vec<f64>,
+}
+
+impl Enumeration {
+    pub fn new(ising: &Ising) -> Result<Self, String> {
+        if ising.model != Model::Ising {
+            return Err("exact enumeration needs Ising spins".to_string());
+        }
+        let occupied: Vec<usize> = (0..ising.lattice.len()).filter(|&idx| ising.occupancy[idx]).collect();
+        if occupied.len() > MAX_ENUMERATION_SITES {
+            return Err(format!(
+                "exact enumeration is limited to {} spins, got {}",
+                MAX_ENUMERATION_SITES,
+                occupied.len()
+            ));
+        }
+
+        let mut copy = ising.clone();
+        copy.magnetic_field_strength = 0.0;
+        occupied.iter().for_each(|&idx| copy.lattice[idx] = 1);
+        let mut energy = copy.total_energy();
+        let mut magnetization = occupied.len() as i32;
+        // Energies are binned to 1e-9, well above the rounding accumulated over the flips
+        let key = |energy: f64, magnetization: i32| ((energy * 1e9).round() as i64, magnetization);
+        let mut counts: HashMap<(i64, i32), (f64, f64)> = HashMap::new();
+        counts.insert(key(energy, magnetization), (energy, 1.0));
+        for k in 1u64..1 << occupied.len() {
+            let idx = occupied[k.trailing_zeros() as usize];
+            let spin = copy.lattice[idx];
+            energy += copy.site_energy(idx, -spin) - copy.site_energy(idx, spin);
+            magnetization -= 2 * spin as i32;
+            copy.lattice[idx] = -spin;
+            counts.entry(key(energy, magnetization)).or_insert((energy, 0.0)).1 += 1.0;
+        }
+
+        let mut states: Vec<(f64, i32, f64)> = counts
+            .into_iter()
+            .map(|((_, magnetization), (energy, count))| (energy, magnetization, count))
+            .collect();
+        states.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        Ok(Self {
+            sites: occupied.len(),
+            states,
+            conditions: conditions(ising),
+        })
+    }
+
+    /// Whether `ising` still has the couplings, fields and vacancies enumerated.
+    pub fn matches(&self, ising: &Ising) -> bool {
+        self.conditions == conditions(ising)
+    }
+
+    pub fn thermodynamics(&self, temperature: f64, field: f64) -> Thermodynamics {
+        let energies: Vec<f64> = self.states.iter().map(|&(e, m, _)| e - field * m as f64).collect();
+        let lowest = energies.iter().copied().fold(f64::INFINITY, f64::min);
+        // Written so that T = 0 keeps the ground states without dividing 0 by 0
+        let (mut z, mut e, mut e2, mut m, mut abs_m, mut m2) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        for (&energy, &(_, magnetization, count)) in energies.iter().zip(self.states.iter()) {
+            let boltzmann = if energy <= lowest { 1.0 } else { (-(energy - lowest) / temperature).exp() };
+            let weight = count * boltzmann;
+            let magnetization = magnetization as f64;
+            z += weight;
+            e += weight * energy;
+            e2 += weight * energy * energy;
+            m += weight * magnetization;
+            abs_m += weight * magnetization.abs();
+            m2 += weight * magnetization * magnetization;
+        }
+        let n = self.sites as f64;
+        let (e, e2, m, abs_m, m2) = (e / z, e2 / z, m / z, abs_m / z, m2 / z);
+        Thermodynamics {
+            free_energy: (lowest - temperature * z.ln()) / n,
+            energy: e / n,
+            magnetization: m / n,
+            absolute_magnetization: Some(abs_m / n),
+            specific_heat: fluctuation(e2 - e * e, n * temperature * temperature),
+            susceptibility: fluctuation(m2 - abs_m * abs_m, n * temperature),
+        }
+    }
+}
+
+/// Square lattice Ising strip of `width` spins around, periodic across and infinitely
+/// long, solved from the largest eigenvalue λ of its column to column transfer matrix:
+/// the free energy per site is -T ln λ / width. Derivatives of ln λ are taken by finite
+/// differences, so ⟨M⟩ and the susceptibility are those of the infinite strip, which has
+/// no spontaneous magnetization.
+#[derive(Debug)]
+pub struct TransferMatrix {
+    pub width: usize,
+    /// Coupling along the strip, between columns.
+    pub horizontal_coupling: f64,
+    /// Coupling across the strip, within a column.
+    pub vertical_coupling: f64,
+}
+
+impl TransferMatrix {
+    pub fn new(width: usize, horizontal_coupling: f64, vertical_coupling: f64) -> Result<Self, String> {
+        if !(1..=MAX_STRIP_WIDTH).contains(&width) {
+            return Err(format!("strip width must be between 1 and {}, got {}", MAX_STRIP_WIDTH, width));
+        }
+        Ok(Self {
+            width,
+            horizontal_coupling,
+            vertical_coupling,
+        })
+    }
+
+    /// ln λ at inverse temperature `beta` and reduced field `beta * h`, by power iteration
+    /// on the symmetric form D^½ K D^½ of the transfer matrix. D is diagonal with the
+    /// Boltzmann weights of a column's own bonds and fields, and K the product of one 2 x 2
+    /// matrix per row for the bonds to the next column, applied a row at a time. Both are
+    /// scaled by their largest entries, which are added back to ln λ.
+    fn ln_eigenvalue(&self, beta: f64, reduced_field: f64) -> f64 {
+        let width = self.width;
+        let num_states = 1usize << width;
+        let spin = |state: usize, row: usize| if state >> row & 1 == 1 { 1.0 } else { -1.0 };
+        let exponents: Vec<f64> = (0..num_states)
+            .map(|state| {
+                // A width of 2 gets both bonds of the periodic square lattice
+                let bonds: f64 = match width {
+                    1 => 0.0,
+                    _ => (0..width).map(|row| spin(state, row) * spin(state, (row + 1) % width)).sum(),
+                };
+                let magnetization: f64 = (0..width).map(|row| spin(state, row)).sum();
+                beta * self.vertical_coupling * bonds + reduced_field * magnetization
+            })
+            .collect();
+        let largest = exponents.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let root_weights: Vec<f64> = exponents.iter().map(|x| (0.5 * (x - largest)).exp()).collect();
+        let coupling = beta * self.horizontal_coupling;
+        let (same, opposite) = if coupling >= 0.0 { (1.0, (-2.0 * coupling).exp()) } else { ((2.0 * coupling).exp(), 1.0) };
+
+        let mut vector = vec![1.0 / (num_states as f64).sqrt(); num_states];
+        let mut eigenvalue = 0.0;
+        for _ in 0..100_000 {
+            let mut next: Vec<f64> = vector.iter().zip(root_weights.iter()).map(|(v, w)| v * w).collect();
+            for row in 0..width {
+                let bit = 1 << row;
+                for state in (0..num_states).filter(|state| state & bit == 0) {
+                    let (a, b) = (next[state], next[state | bit]);
+                    next[state] = same * a + opposite * b;
+                    next[state | bit] = opposite * a + same * b;
+                }
+            }
+            next.iter_mut().zip(root_weights.iter()).for_each(|(v, w)| *v *= w);
+
+            // Rayleigh quotient of the normalized vector
+            let estimate: f64 = next.iter().zip(vector.iter()).map(|(a, b)| a * b).sum();
+            let norm = next.iter().map(|v| v * v).sum::<f64>().sqrt();
+            vector = next.into_iter().map(|v| v / norm).collect();
+            let converged = (estimate - eigenvalue).abs() <= 1e-15 * estimate;
+            eigenvalue = estimate;
+            if converged {
+                break;
+            }
+        }
+        eigenvalue.ln() + largest + width as f64 * coupling.abs()
+    }
+
+    pub fn thermodynamics(&self, temperature: f64, field: f64) -> Thermodynamics {
+        let beta = 1.0 / temperature;
+        let x = beta * field;
+        let n = self.width as f64;
+        // Relative step in β and absolute step in βh
+        let step = 1e-4;
+        let ln_lambda = self.ln_eigenvalue(beta, x);
+        let (beta_up, beta_down) = (beta * (1.0 + step), beta * (1.0 - step));
+        let ln_lambda_hot = self.ln_eigenvalue(beta_down, beta_down * field);
+        let ln_lambda_cold = self.ln_eigenvalue(beta_up, beta_up * field);
+        let ln_lambda_up = self.ln_eigenvalue(beta, x + step);
+        let ln_lambda_down = self.ln_eigenvalue(beta, x - step);
+
+        let magnetization = (ln_lambda_up - ln_lambda_down) / (2.0 * step * n);
+        let derivative = (ln_lambda_cold - ln_lambda_hot) / (2.0 * step * beta);
+        let curvature = (ln_lambda_cold - 2.0 * ln_lambda + ln_lambda_hot) / (step * step * beta * beta);
+        Thermodynamics {
+            free_energy: -temperature * ln_lambda / n,
+            energy: -derivative / n,
+            magnetization,
+            absolute_magnetization: None,
+            specific_heat: beta * beta * curvature / n,
+            susceptibility: beta * (ln_lambda_up - 2.0 * ln_lambda + ln_lambda_down) / (step * step * n),
+        }
+    }
+}
+
+/// Running Monte Carlo averages of the energy and magnetization per site, started over
+/// whenever the temperature, field, couplings or disorder they were sampled at change.
+#[derive(Debug, Default)]
+pub struct Sampler {
+    conditions: Vec<f64>,
+    samples: usize,
+    /// Sums of e, e², m, |m| and m² per site.
+    sums: [f64; 5],
+}
+
+impl Sampler {
+    pub fn add(&mut self, ising: &Ising) {
+        let mut current = conditions(ising);
+        current.extend([ising.temperature, ising.magnetic_moment * ising.magnetic_field_strength]);
+        if current != self.conditions {
+            self.conditions = current;
+            self.samples = 0;
+            self.sums = [0.0; 5];
+        }
+        let (e, m) = (ising.energy(), ising.magnetization());
+        for (sum, value) in self.sums.iter_mut().zip([e, e * e, m, m.abs(), m * m]) {
+            *sum += value;
+        }
+        self.samples += 1;
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Estimates from the samples so far, with no free energy.
+    pub fn thermodynamics(&self, ising: &Ising) -> Thermodynamics {
+        let count = self.samples.max(1) as f64;
+        let [e, e2, m, abs_m, m2] = self.sums.map(|sum| sum / count);
+        let n = ising.num_occupied() as f64;
+        let temperature = ising.temperature;
+        Thermodynamics {
+            free_energy: f64::NAN,
+            energy: e,
+            magnetization: m,
+            absolute_magnetization: Some(abs_m),
+            specific_heat: fluctuation(n * (e2 - e * e), temperature * temperature),
+            susceptibility: fluctuation(n * (m2 - abs_m * abs_m), temperature),
+        }
+    }
+}
+
+/// Variance over a power of the temperature, taken as 0 at T = 0.
+pub fn fluctuation(variance: f64, scale: f64) -> f64 {
+    if scale > 0.0 {
+        variance.max(0.0) / scale
+    } else {
+        0.0
+    }
+}
+
+/// Everything besides the uniform field and temperature that the energies depend on.
+pub fn conditions(ising: &Ising) -> Vec<f64> {
+    let couplings = [
+        ising.horizontal_coupling,
+        ising.vertical_coupling,
+        ising.diagonal_coupling,
+        ising.axial_coupling,
+    ];
+    let occupancy = ising.occupancy.iter().map(|&occupied| occupied as u8 as f64);
+    let weights = ising.bonds.iter().flatten().map(|bond| bond.weight);
+    couplings
+        .into_iter()
+        .chain(ising.fields.iter().copied())
+        .chain(occupancy)
+        .chain(weights)
+        .collect()
+}
+
+/// Monte Carlo estimates at a series of temperatures to compare with exact results,
+/// heating from the ordered state and carrying the configuration along.
+#[derive(Debug)]
+pub struct Validation {
+    /// Measurement sweeps at every temperature, after a quarter as many to equilibrate.
+    pub sweeps: usize,
+    pub algorithm: Algorithm,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            sweeps: 1000,
+            algorithm: Algorithm::Metropolis,
+        }
+    }
+}
+
+impl Validation {
+    pub fn run<R: Rng + ?Sized>(&self, ising: &mut Ising, temperatures: &[f64], field: f64, rng: &mut R) -> Vec<Thermodynamics> {
+        ising.magnetic_moment = 1.0;
+        ising.magnetic_field_strength = field;
+        for (spin, &occupied) in ising.lattice.iter_mut().zip(ising.occupancy.iter()) {
+            *spin = occupied as i8;
+        }
+        temperatures
+            .iter()
+            .map(|&temperature| {
+                ising.temperature = temperature;
+                let num_sites = ising.lattice.len();
+                for _ in 0..self.sweeps / 4 {
+                    self.algorithm.run(ising, num_sites, rng);
+                }
+                let mut sampler = Sampler::default();
+                for _ in 0..self.sweeps {
+                    self.algorithm.run(ising, num_sites, rng);
+                    sampler.add(ising);
+                }
+                sampler.thermodynamics(ising)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lattice::Rectangular;
+    use crate::onsager::Torus;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::rc::Rc;
+
+    fn ising(lattice: Rectangular) -> Ising {
+        Ising::new(Rc::new(lattice), &mut StdRng::seed_from_u64(0))
+    }
+
+    /// Per site free energy -T ln Z / N of a density of states given as (E, count).
+    fn free_energy(states: &[(f64, f64)], sites: f64, temperature: f64) -> f64 {
+        let z: f64 = states.iter().map(|&(energy, count)| count * (-energy / temperature).exp()).sum();
+        -temperature * z.ln() / sites
+    }
+
+    #[test]
+    fn enumerates_the_2x2_torus() {
+        // Each pair of neighbours is bonded twice around a side of 2
+        let enumeration = Enumeration::new(&ising(Rectangular::square(2))).unwrap();
+        for temperature in [1.0, 2.269, 4.0] {
+            let expected = free_energy(&[(-8.0, 2.0), (0.0, 12.0), (8.0, 2.0)], 4.0, temperature);
+            let exact = enumeration.thermodynamics(temperature, 0.0);
+            assert!((exact.free_energy - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn enumerates_the_4x4_torus() {
+        let density_of_states = [
+            (-32.0, 2.0),
+            (-24.0, 32.0),
+            (-20.0, 64.0),
+            (-16.0, 424.0),
+            (-12.0, 1728.0),
+            (-8.0, 6688.0),
+            (-4.0, 13568.0),
+            (0.0, 20524.0),
+            (4.0, 13568.0),
+            (8.0, 6688.0),
+            (12.0, 1728.0),
+            (16.0, 424.0),
+            (20.0, 64.0),
+            (24.0, 32.0),
+            (32.0, 2.0),
+        ];
+        let enumeration = Enumeration::new(&ising(Rectangular::square(4))).unwrap();
+        for temperature in [1.0, 2.269, 4.0] {
+            let expected = free_energy(&density_of_states, 16.0, temperature);
+            let exact = enumeration.thermodynamics(temperature, 0.0);
+            assert!((exact.free_energy - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn transfer_matrix_matches_a_long_periodic_strip() {
+        // 2 x 10 torus, against the infinite strip of width 2 up to the subleading
+        // eigenvalues, which (λ_1 / λ_0)^10 leaves negligible at high temperature
+        let strip = ising(Rectangular {
+            height: 2,
+            ..Rectangular::square(10)
+        });
+        let enumeration = Enumeration::new(&strip).unwrap();
+        let transfer_matrix = TransferMatrix::new(2, 1.0, 1.0).unwrap();
+        for (temperature, field) in [(4.0, 0.0), (5.0, 0.2)] {
+            let exact = enumeration.thermodynamics(temperature, field);
+            let strip = transfer_matrix.thermodynamics(temperature, field);
+            assert!((exact.free_energy - strip.free_energy).abs() < 1e-4);
+            assert!((exact.energy - strip.energy).abs() < 1e-3);
+            assert!((exact.magnetization - strip.magnetization).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn enumerates_the_5x5_torus() {
+        // The largest square enumerated, of odd side, against Kaufman's finite torus
+        let ising = ising(Rectangular::square(5));
+        let enumeration = Enumeration::new(&ising).unwrap();
+        let torus = Torus::of(&ising).unwrap();
+        for temperature in [1.0, 2.269, 4.0] {
+            let exact = enumeration.thermodynamics(temperature, 0.0);
+            let free_energy = -temperature * torus.ln_partition_function(1.0 / temperature) / 25.0;
+            assert!((exact.free_energy - free_energy).abs() < 1e-10);
+            assert!((exact.energy - torus.energy(temperature)).abs() < 1e-6);
+            assert!((exact.specific_heat - torus.specific_heat(temperature)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn kaufman_matches_the_4x4_torus() {
+        let ising = ising(Rectangular::square(4));
+        let enumeration = Enumeration::new(&ising).unwrap();
+        let torus = Torus::of(&ising).unwrap();
+        for temperature in [1.0, 2.269, 4.0] {
+            let exact = enumeration.thermodynamics(temperature, 0.0);
+            let free_energy = -temperature * torus.ln_partition_function(1.0 / temperature) / 16.0;
+            assert!((exact.free_energy - free_energy).abs() < 1e-10);
+            assert!((exact.energy - torus.energy(temperature)).abs() < 1e-6);
+            assert!((exact.specific_heat - torus.specific_heat(temperature)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn monte_carlo_matches_enumeration() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let temperatures = [1.5, 3.0];
+        for algorithm in [Algorithm::Metropolis, Algorithm::Wolff] {
+            let mut ising = ising(Rectangular::square(4));
+            let enumeration = Enumeration::new(&ising).unwrap();
+            let validation = Validation { sweeps: 30000, algorithm };
+            let estimates = validation.run(&mut ising, &temperatures, 0.0, &mut rng);
+            for (&temperature, estimate) in temperatures.iter().zip(estimates.iter()) {
+                let exact = enumeration.thermodynamics(temperature, 0.0);
+                let (m, m_exact) = (estimate.absolute_magnetization.unwrap(), exact.absolute_magnetization.unwrap());
+                // A few standard errors of 30000 correlated sweeps of 16 spins
+                assert!((estimate.energy - exact.energy).abs() < 0.03, "{} at T = {}", algorithm, temperature);
+                assert!((m - m_exact).abs() < 0.03, "{} at T = {}", algorithm, temperature);
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use crate::vector::{Vec2, Vec3};
+
+/// Periodic lattices, laid out on an L x L grid of positions, or L x L x L for cubic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Geometry {
+    Square,
+    /// Square grid plus the (1, -1) diagonal, so six neighbours per site.
+    Triangular,
+    /// Brick wall: horizontal neighbours plus one vertical neighbour, up or down
+    /// alternating like a checkerboard, so three neighbours per site.
+    Honeycomb,
+    /// Triangular grid without the sites where both coordinates are odd, leaving
+    /// corner-sharing triangles with four neighbours per site.
+    Kagome,
+    /// Simple cubic, six neighbours per site. Drawn one z = const slice at a time.
+    Cubic,
+}
+
+const SQUARE_OFFSETS: [Vec3; 4] = [
+    Vec3 { x: 1, y: 0, z: 0 },
+    Vec3 { x: -1, y: 0, z: 0 },
+    Vec3 { x: 0, y: 1, z: 0 },
+    Vec3 { x: 0, y: -1, z: 0 },
+];
+
+const TRIANGULAR_OFFSETS: [Vec3; 6] = [
+    Vec3 { x: 1, y: 0, z: 0 },
+    Vec3 { x: -1, y: 0, z: 0 },
+    Vec3 { x: 0, y: 1, z: 0 },
+    Vec3 { x: 0, y: -1, z: 0 },
+    Vec3 { x: 1, y: -1, z: 0 },
+    Vec3 { x: -1, y: 1, z: 0 },
+];
+
+const CUBIC_OFFSETS: [Vec3; 6] = [
+    Vec3 { x: 1, y: 0, z: 0 },
+    Vec3 { x: -1, y: 0, z: 0 },
+    Vec3 { x: 0, y: 1, z: 0 },
+    Vec3 { x: 0, y: -1, z: 0 },
+    Vec3 { x: 0, y: 0, z: 1 },
+    Vec3 { x: 0, y: 0, z: -1 },
+];
+
+impl Geometry {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "square" => Ok(Geometry::Square),
+            "triangular" => Ok(Geometry::Triangular),
+            "honeycomb" => Ok(Geometry::Honeycomb),
+            "kagome" => Ok(Geometry::Kagome),
+            "cubic" => Ok(Geometry::Cubic),
+            _ => Err(format!("unrecognized geometry '{}'", name)),
+        }
+    }
+
+    pub fn num_sites(&self, size: usize) -> usize {
+        match self {
+            Geometry::Square | Geometry::Triangular | Geometry::Honeycomb => size.pow(2),
+            Geometry::Kagome => 3 * size.pow(2) / 4,
+            Geometry::Cubic => size.pow(3),
+        }
+    }
+
+    /// Number of z = const slices.
+    pub fn depth(&self, size: usize) -> usize {
+        match self {
+            Geometry::Cubic => size,
+            _ => 1,
+        }
+    }
+
+    /// Index of the site at a grid position, wrapped periodically. Kagome rows alternate
+    /// between `size` sites and `size / 2` sites on the even columns.
+    pub fn index(&self, size: usize, pos: &Vec3) -> usize {
+        let x = pos.x.rem_euclid(size as i32) as usize;
+        let y = pos.y.rem_euclid(size as i32) as usize;
+        let z = pos.z.rem_euclid(size as i32) as usize;
+        match self {
+            Geometry::Square | Geometry::Triangular | Geometry::Honeycomb => y * size + x,
+            Geometry::Kagome => {
+                let row_pair = (y / 2) * (3 * size / 2);
+                if y.is_multiple_of(2) {
+                    row_pair + x
+                } else {
+                    row_pair + size + x / 2
+                }
+            }
+            Geometry::Cubic => (z * size + y) * size + x,
+        }
+    }
+
+    /// Grid position of a site, the inverse of `index`.
+    pub fn position(&self, size: usize, idx: usize) -> Vec3 {
+        match self {
+            Geometry::Square | Geometry::Triangular | Geometry::Honeycomb | Geometry::Cubic => Vec3 {
+                x: (idx % size) as i32,
+                y: (idx / size % size) as i32,
+                z: (idx / size.pow(2)) as i32,
+            },
+            Geometry::Kagome => {
+                let (row_pair, k) = (idx / (3 * size / 2), idx % (3 * size / 2));
+                if k < size {
+                    Vec3 {
+                        x: k as i32,
+                        y: 2 * row_pair as i32,
+                        z: 0,
+                    }
+                } else {
+                    Vec3 {
+                        x: 2 * (k - size) as i32,
+                        y: 2 * row_pair as i32 + 1,
+                        z: 0,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Grid positions of the neighbours of a site, before periodic wrapping.
+    pub fn neighbors(&self, pos: &Vec3) -> Vec<Vec3> {
+        let displaced = |offsets: &[Vec3]| -> Vec<Vec3> {
+            offsets
+                .iter()
+                .map(|offset| Vec3 {
+                    x: pos.x + offset.x,
+                    y: pos.y + offset.y,
+                    z: pos.z + offset.z,
+                })
+                .collect()
+        };
+        match self {
+            Geometry::Square => displaced(&SQUARE_OFFSETS),
+            Geometry::Triangular => displaced(&TRIANGULAR_OFFSETS),
+            Geometry::Honeycomb => {
+                let vertical = if (pos.x + pos.y).rem_euclid(2) == 0 { 1 } else { -1 };
+                displaced(&[
+                    Vec3 { x: 1, y: 0, z: 0 },
+                    Vec3 { x: -1, y: 0, z: 0 },
+                    Vec3 { x: 0, y: vertical, z: 0 },
+                ])
+            }
+            Geometry::Kagome => displaced(&TRIANGULAR_OFFSETS)
+                .into_iter()
+                .filter(|n| n.x.rem_euclid(2) == 0 || n.y.rem_euclid(2) == 0)
+                .collect(),
+            Geometry::Cubic => displaced(&CUBIC_OFFSETS),
+        }
+    }
+
+    /// Where a site is drawn. Triangular and kagome rows are sheared by half a site per
+    /// row, so the lattice shows as a rhombus with 60° angles. Cubic slices drop z.
+    pub fn pixel(&self, pos: &Vec3) -> Vec2 {
+        match self {
+            Geometry::Square | Geometry::Honeycomb | Geometry::Cubic => Vec2 { x: pos.x, y: pos.y },
+            Geometry::Triangular | Geometry::Kagome => Vec2 {
+                x: pos.x + pos.y / 2,
+                y: pos.y,
+            },
+        }
+    }
+
+    /// Width and height of the drawing of an L x L lattice or slice, in pixels.
+    pub fn canvas_size(&self, size: usize) -> (usize, usize) {
+        match self {
+            Geometry::Square | Geometry::Honeycomb | Geometry::Cubic => (size, size),
+            Geometry::Triangular | Geometry::Kagome => (size + size / 2, size),
+        }
+    }
+
+    /// Critical temperature of the ferromagnet with unit coupling, exact in two dimensions
+    /// and from high precision Monte Carlo for the cubic lattice.
+    pub fn critical_temperature(&self) -> f64 {
+        match self {
+            Geometry::Square => 2.0 / (1.0 + 2f64.sqrt()).ln(),
+            Geometry::Triangular => 4.0 / 3f64.ln(),
+            Geometry::Honeycomb => 2.0 / (2.0 + 3f64.sqrt()).ln(),
+            Geometry::Kagome => 4.0 / (3.0 + 2.0 * 3f64.sqrt()).ln(),
+            Geometry::Cubic => 4.511_52,
+        }
+    }
+
+    /// Whether antiferromagnetic couplings frustrate the lattice, which then has no
+    /// finite temperature transition.
+    pub fn is_frustrated(&self) -> bool {
+        matches!(self, Geometry::Triangular | Geometry::Kagome)
+    }
+
+    pub fn is_exactly_solved(&self) -> bool {
+        *self != Geometry::Cubic
+    }
+}
+
+/// Onsager's critical temperature of the anisotropic square lattice, the root of
+/// sinh(2|J_x| / T) sinh(2|J_y| / T) = 1, found by bisection. It falls to 0 as either
+/// coupling vanishes and the lattice decouples into chains.
+pub fn square_critical_temperature(horizontal: f64, vertical: f64) -> f64 {
+    let (jx, jy) = (horizontal.abs(), vertical.abs());
+    if jx == 0.0 || jy == 0.0 {
+        return 0.0;
+    }
+    // The product falls with T, and is below 1 at the mean-field bound 2(|J_x| + |J_y|)
+    let (mut low, mut high) = (0.0, 2.0 * (jx + jy));
+    for _ in 0..100 {
+        let t = 0.5 * (low + high);
+        if (2.0 * jx / t).sinh() * (2.0 * jy / t).sinh() > 1.0 {
+            low = t;
+        } else {
+            high = t;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// Critical temperature of the q-state Potts ferromagnet on the anisotropic square lattice,
+/// the self-dual point (exp(J_x / T) - 1)(exp(J_y / T) - 1) = q, found by bisection. With
+/// equal couplings it is J / ln(1 + √q). The transition is continuous for q ≤ 4 and first
+/// order above.
+pub fn square_potts_critical_temperature(horizontal: f64, vertical: f64, states: usize) -> f64 {
+    let (jx, jy) = (horizontal.abs(), vertical.abs());
+    if jx == 0.0 || jy == 0.0 {
+        return 0.0;
+    }
+    let (mut low, mut high) = (0.0, 2.0 * (jx + jy));
+    for _ in 0..100 {
+        let t = 0.5 * (low + high);
+        if (jx / t).exp_m1() * (jy / t).exp_m1() > states as f64 {
+            low = t;
+        } else {
+            high = t;
+        }
+    }
+    0.5 * (low + high)
+}
@@ -0,0 +1,216 @@
+use rand::Rng;
+use std::f64::consts::PI;
+
+use crate::ising::Ising;
+
+/// High precision Monte Carlo critical temperature of the simple cubic Heisenberg model
+/// with unit coupling.
+const CUBIC_CRITICAL_TEMPERATURE: f64 = 1.443;
+
+/// Unit 3-vector spins S_i on the sites of an `Ising` system, which provides the bonds,
+/// couplings, temperature and fields: the classical Heisenberg model
+/// H = -Σ J_ij S_i·S_j - Σ h_i S_i^z - D Σ (S_i^z)². Every field points along z, as do
+/// fixed boundary spins. The single-ion anisotropy D > 0 favours the z axis, and
+/// approaches the Ising model as it grows, while D < 0 favours the xy plane.
+#[derive(Debug)]
+pub struct HeisenbergSpins {
+    pub spins: Vec<[f64; 3]>,
+    pub anisotropy: f64,
+    /// Heat bath instead of Metropolis updates.
+    pub heat_bath: bool,
+    /// Radius of the Metropolis displacements, tuned between runs towards an acceptance
+    /// rate of one half.
+    window: f64,
+}
+
+impl HeisenbergSpins {
+    /// Spins on the sites of `ising`, drawn independently.
+    pub fn new<R: Rng + ?Sized>(ising: &Ising, anisotropy: f64, rng: &mut R) -> Self {
+        let mut spins = Self {
+            spins: vec![[0.0, 0.0, 1.0]; ising.lattice.len()],
+            anisotropy,
+            heat_bath: false,
+            window: 1.0,
+        };
+        spins.randomize(rng);
+        spins
+    }
+
+    pub fn randomize<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for spin in self.spins.iter_mut() {
+            *spin = random_unit_vector(rng);
+        }
+    }
+
+    /// Field acting on a site from its neighbours and external fields, not counting the
+    /// anisotropy.
+    fn local_field(&self, ising: &Ising, idx: usize) -> [f64; 3] {
+        let mut field = [0.0; 3];
+        for bond in ising.bonds[idx].iter().filter(|bond| ising.occupancy[bond.site]) {
+            let coupling = ising.coupling(bond.kind) * bond.weight;
+            for (h, s) in field.iter_mut().zip(self.spins[bond.site].iter()) {
+                *h += coupling * s;
+            }
+        }
+        field[2] += external_field(ising, idx);
+        field
+    }
+
+    /// Metropolis update of a random site, proposing the current spin displaced by a
+    /// uniform draw from a ball and normalized, which is as likely as the reverse.
+    /// Returns whether it was accepted.
+    pub fn metropolis_step<R: Rng + ?Sized>(&mut self, ising: &Ising, rng: &mut R) -> bool {
+        let idx = rng.gen_range(0..self.spins.len());
+        if !ising.occupancy[idx] {
+            return false;
+        }
+        let old = self.spins[idx];
+        let displacement = random_unit_vector(rng);
+        let radius = self.window * rng.gen::<f64>().cbrt();
+        let new = normalized([
+            old[0] + radius * displacement[0],
+            old[1] + radius * displacement[1],
+            old[2] + radius * displacement[2],
+        ]);
+        let field = self.local_field(ising, idx);
+        let energy_diff = self.site_energy(&field, &new) - self.site_energy(&field, &old);
+        if energy_diff <= 0.0 || rng.gen::<f64>() < (-energy_diff / ising.temperature).exp() {
+            self.spins[idx] = new;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Heat bath update of a random site: draws the spin from its Boltzmann distribution
+    /// in the local field, exp(β|h| cos θ) about the field direction, then accepts it with
+    /// the Metropolis probability of the change in anisotropy energy, which the draw
+    /// leaves out.
+    pub fn heat_bath_step<R: Rng + ?Sized>(&mut self, ising: &Ising, rng: &mut R) {
+        let idx = rng.gen_range(0..self.spins.len());
+        if !ising.occupancy[idx] {
+            return;
+        }
+        let field = self.local_field(ising, idx);
+        let strength = dot(&field, &field).sqrt();
+        let x = strength / ising.temperature;
+        let u: f64 = rng.gen();
+        let cos_theta = if strength == 0.0 || x < 1e-10 {
+            2.0 * u - 1.0
+        } else {
+            (1.0 + (1.0 - u * (1.0 - (-2.0 * x).exp())).ln() / x).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = rng.gen_range(0.0..2.0 * PI);
+
+        // Orthonormal frame (a, b, axis) around the field, or z without one
+        let axis = if strength == 0.0 { [0.0, 0.0, 1.0] } else { normalized(field) };
+        let helper = if axis[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+        let a = normalized(cross(&axis, &helper));
+        let b = cross(&axis, &a);
+        let new: [f64; 3] = std::array::from_fn(|k| {
+            sin_theta * (phi.cos() * a[k] + phi.sin() * b[k]) + cos_theta * axis[k]
+        });
+
+        let old = self.spins[idx];
+        let energy_diff = -self.anisotropy * (new[2] * new[2] - old[2] * old[2]);
+        if energy_diff <= 0.0 || rng.gen::<f64>() < (-energy_diff / ising.temperature).exp() {
+            self.spins[idx] = new;
+        }
+    }
+
+    fn site_energy(&self, field: &[f64; 3], spin: &[f64; 3]) -> f64 {
+        -dot(field, spin) - self.anisotropy * spin[2] * spin[2]
+    }
+
+    /// `steps` heat bath or Metropolis updates.
+    pub fn run<R: Rng + ?Sized>(&mut self, ising: &Ising, steps: usize, rng: &mut R) {
+        if self.heat_bath {
+            for _ in 0..steps {
+                self.heat_bath_step(ising, rng);
+            }
+            return;
+        }
+        let accepted = (0..steps).filter(|_| self.metropolis_step(ising, rng)).count();
+        if steps > 0 {
+            let factor = if 2 * accepted > steps { 1.1 } else { 0.9 };
+            self.window = (self.window * factor).clamp(0.01, 2.0);
+        }
+    }
+
+    /// Mean spin per occupied site.
+    pub fn mean_spin(&self, ising: &Ising) -> [f64; 3] {
+        let mut total = [0.0; 3];
+        for (spin, _) in self.spins.iter().zip(ising.occupancy.iter()).filter(|(_, &occupied)| occupied) {
+            for (t, s) in total.iter_mut().zip(spin.iter()) {
+                *t += s;
+            }
+        }
+        total.map(|t| t / ising.num_occupied() as f64)
+    }
+
+    /// Length of the mean spin per occupied site.
+    pub fn magnetization(&self, ising: &Ising) -> f64 {
+        let mean = self.mean_spin(ising);
+        dot(&mean, &mean).sqrt()
+    }
+
+    /// Total energy per occupied site.
+    pub fn energy(&self, ising: &Ising) -> f64 {
+        let total: f64 = (0..self.spins.len())
+            .filter(|&idx| ising.occupancy[idx])
+            .map(|idx| {
+                let spin = &self.spins[idx];
+                let mut field = self.local_field(ising, idx);
+                let external = external_field(ising, idx);
+                field[2] -= external;
+                // Bonds are seen from both ends, external fields once
+                -0.5 * dot(&field, spin) - external * spin[2] - self.anisotropy * spin[2] * spin[2]
+            })
+            .sum();
+        total / ising.num_occupied() as f64
+    }
+
+    /// Critical temperature of the isotropic ferromagnet with equal couplings, if known:
+    /// none in two dimensions, where continuous spins cannot order at T > 0
+    /// (Mermin-Wagner), and from Monte Carlo on the cubic lattice.
+    pub fn critical_temperature(&self, ising: &Ising) -> Option<f64> {
+        let coupling = ising.horizontal_coupling;
+        let isotropic = self.anisotropy == 0.0 && coupling > 0.0 && ising.vertical_coupling == coupling;
+        // Graphs have no dimension
+        if !isotropic || ising.geometry.canvas_size() == (0, 0) {
+            return None;
+        }
+        match ising.geometry.depth() {
+            1 => Some(0.0),
+            _ => Some(CUBIC_CRITICAL_TEMPERATURE * coupling),
+        }
+    }
+}
+
+/// Uniform, local and boundary wall field along z acting on a site.
+fn external_field(ising: &Ising, idx: usize) -> f64 {
+    let walls: f64 = ising.walls[idx].iter().map(|wall| ising.coupling(wall.kind) * wall.spin).sum();
+    ising.magnetic_moment * ising.magnetic_field_strength + ising.fields[idx] + walls
+}
+
+/// Direction drawn uniformly from the unit sphere.
+fn random_unit_vector<R: Rng + ?Sized>(rng: &mut R) -> [f64; 3] {
+    let z: f64 = rng.gen_range(-1.0..1.0);
+    let phi = rng.gen_range(0.0..2.0 * PI);
+    let r = (1.0 - z * z).sqrt();
+    [r * phi.cos(), r * phi.sin(), z]
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalized(v: [f64; 3]) -> [f64; 3] {
+    let length = dot(&v, &v).sqrt();
+    v.map(|c| c / length)
+}
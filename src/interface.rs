@@ -0,0 +1,92 @@
+use rand::Rng;
+use std::rc::Rc;
+
+use crate::ising::Ising;
+use crate::lattice::{Boundary, Rectangular};
+
+/// Interface free energy of the L x L square lattice ferromagnet, by thermodynamic
+/// integration between periodic and antiperiodic boundaries along x. The bonds of the seam
+/// between the last and first column are given coupling λJ, and since
+/// d(-βF)/dλ = βJ⟨Σ_seam σ_i σ_j⟩, the cost of the domain wall forced by the antiperiodic
+/// boundary is F(-1) - F(1) = ∫_{-1}^{1} J⟨Σ_seam σ_i σ_j⟩_λ dλ.
+#[derive(Debug)]
+pub struct Integration {
+    /// Evenly spaced values of λ, integrated with the trapezoid rule.
+    pub points: usize,
+    /// Measurement sweeps at every λ, after a quarter as many to equilibrate.
+    pub sweeps: usize,
+}
+
+impl Default for Integration {
+    fn default() -> Self {
+        Self {
+            points: 21,
+            sweeps: 1000,
+        }
+    }
+}
+
+impl Integration {
+    /// Interface tension σ = ΔF / L. λ is lowered from 1 to -1 in small steps, starting from
+    /// the ordered state, so the wall can grow out of the seam rather than nucleate.
+    pub fn run<R: Rng + ?Sized>(&self, size: usize, coupling: f64, temperature: f64, rng: &mut R) -> f64 {
+        let mut ising = Ising::new(Rc::new(Rectangular {
+            boundaries: [Boundary::Antiperiodic, Boundary::Periodic],
+            ..Rectangular::square(size)
+        }), rng);
+        ising.horizontal_coupling = coupling;
+        ising.vertical_coupling = coupling;
+        ising.temperature = temperature;
+        ising.lattice.iter_mut().for_each(|s| *s = 1);
+
+        let seam: Vec<(usize, usize)> = (0..ising.bonds.len())
+            .flat_map(|i| ising.bonds[i].iter().map(move |bond| (i, bond.site, bond.weight)))
+            .filter(|&(i, j, weight)| weight < 0.0 && i < j)
+            .map(|(i, j, _)| (i, j))
+            .collect();
+
+        let points = self.points.max(2);
+        let step = 2.0 / (points - 1) as f64;
+        let mut free_energy = 0.0;
+        for k in 0..points {
+            let lambda = 1.0 - k as f64 * step;
+            set_seam(&mut ising, &seam, lambda);
+
+            for _ in 0..(self.sweeps / 4) * ising.lattice.len() {
+                ising.monte_carlo_step(rng);
+            }
+            let mut correlation = 0.0;
+            for _ in 0..self.sweeps {
+                for _ in 0..ising.lattice.len() {
+                    ising.monte_carlo_step(rng);
+                }
+                correlation += seam
+                    .iter()
+                    .map(|&(i, j)| (ising.lattice[i] * ising.lattice[j]) as f64)
+                    .sum::<f64>();
+            }
+            correlation /= self.sweeps.max(1) as f64;
+
+            let weight = if k == 0 || k == points - 1 { 0.5 } else { 1.0 };
+            free_energy += weight * step * coupling * correlation;
+        }
+        free_energy / size as f64
+    }
+}
+
+/// Gives the seam bonds coupling `lambda`, from both of their ends.
+fn set_seam(ising: &mut Ising, seam: &[(usize, usize)], lambda: f64) {
+    for &(i, j) in seam {
+        for (a, b) in [(i, j), (j, i)] {
+            if let Some(bond) = ising.bonds[a].iter_mut().find(|bond| bond.site == b) {
+                bond.weight = lambda;
+            }
+        }
+    }
+}
+
+/// Onsager's interface tension of the square lattice, σ = 2J + T ln tanh(J / T) below the
+/// critical temperature and 0 above it.
+pub fn exact_tension(coupling: f64, temperature: f64) -> f64 {
+    (2.0 * coupling + temperature * (coupling / temperature).tanh().ln()).max(0.0)
+}
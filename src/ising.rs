@@ -1,161 +1,454 @@
 use rand::Rng;
-use either::Either;
-use std::iter::once;
+use std::collections::VecDeque;
+use std::rc::Rc;
 
-use crate::vector::{Vec2, transpose};
+use crate::geometry::Geometry;
+use crate::lattice::{Graph, Lattice};
+use crate::problem::Problem;
 
-#[derive(Debug)]
+/// Width in pixels of the strip that graphs without a layout are drawn as.
+const STRIP_WIDTH: usize = 128;
+
+/// Which coupling constant a bond carries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BondKind {
+    /// Along x.
+    Horizontal,
+    /// Along y, or any other direction off the x axis.
+    Vertical,
+    /// Second neighbour across the diagonal of a square plaquette.
+    Diagonal,
+    /// Third neighbour two steps along x, as in the ANNNI model.
+    Axial,
+}
+
+/// One end of a bond as seen from a site: the neighbouring site and the bond's coupling
+/// relative to the coupling constant of its kind.
+#[derive(Debug, Clone, Copy)]
+pub struct Bond {
+    pub site: usize,
+    pub weight: f64,
+    pub kind: BondKind,
+}
+
+/// Values a spin can take, and how spins interact.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    /// σ = ±1, with bond energy -J σ_i σ_j.
+    Ising,
+    /// σ ∈ {1, ..., q}, with bond energy -J δ(σ_i, σ_j). Fields favour state 1, and fixed
+    /// boundary spins are state 1 for up and state 2 for down.
+    Potts { states: i8 },
+    /// Blume-Capel spins σ ∈ {-1, 0, +1}, with the Ising bond energy and a crystal field
+    /// D σ² that favours the zero state for D > 0.
+    BlumeCapel,
+}
+
+/// Bond from a site to a boundary spin that never flips.
+#[derive(Debug, Clone, Copy)]
+pub struct Wall {
+    pub spin: f64,
+    pub kind: BondKind,
+}
+
+#[derive(Debug, Clone)]
 pub struct Ising {
-    pub size: usize,
+    /// Sites and bonds, shared between copies of the system.
+    pub geometry: Rc<dyn Lattice>,
+    pub model: Model,
     pub lattice: Vec<i8>,
+    /// False for non-magnetic vacancies, whose spin is held at 0.
+    pub occupancy: Vec<bool>,
+    pub bonds: Vec<Vec<Bond>>,
+    pub fields: Vec<f64>,
+    /// Fixed boundary spins bonded to each site.
+    pub walls: Vec<Vec<Wall>>,
     pub temperature: f64,
-    pub coupling_constant: f64,
+    pub horizontal_coupling: f64,
+    pub vertical_coupling: f64,
+    pub diagonal_coupling: f64,
+    pub axial_coupling: f64,
     pub magnetic_moment: f64,
     pub magnetic_field_strength: f64,
+    /// D of the Blume-Capel crystal field term.
+    pub crystal_field: f64,
 }
 
 impl Ising {
-    pub fn new(size: usize) -> Self {
-        let mut lattice = vec![0 as i8; size.pow(2)];
+    /// Builds a system of randomly drawn spins on `geometry`.
+    pub fn new<R: Rng + ?Sized>(geometry: Rc<dyn Lattice>, rng: &mut R) -> Self {
+        let num_sites = geometry.num_sites();
+        let bonds = (0..num_sites).map(|idx| geometry.neighbors(idx)).collect();
+        let walls = (0..num_sites).map(|idx| geometry.walls(idx)).collect();
+        let mut ising = Self {
+            geometry,
+            model: Model::Ising,
+            lattice: vec![0; num_sites],
+            occupancy: vec![true; num_sites],
+            bonds,
+            fields: vec![0.0; num_sites],
+            walls,
+            temperature: Geometry::Square.critical_temperature(),
+            horizontal_coupling: 1.0,
+            vertical_coupling: 1.0,
+            diagonal_coupling: 0.0,
+            axial_coupling: 0.0,
+            magnetic_moment: 0.0,
+            magnetic_field_strength: 0.0,
+            crystal_field: 0.0,
+        };
+        ising.randomize(rng);
+        ising
+    }
+
+    /// Builds a system with arbitrary couplings J_ij and local fields h_i on the graph of
+    /// its couplings.
+    pub fn from_problem<R: Rng + ?Sized>(problem: &Problem, rng: &mut R) -> Self {
+        let edges: Vec<(usize, usize)> = problem.couplings.iter().map(|&(i, j, _)| (i, j)).collect();
+        let mut ising = Self::new(Rc::new(Graph::from_edges(problem.num_spins, &edges)), rng);
 
-        for x in lattice.iter_mut() {
-            *x = 2 * (rand::random::<f64>().round() as i8) - 1;
+        // Graph::from_edges lists the bonds of each site in edge order
+        let mut next = vec![0; problem.num_spins];
+        for &(i, j, coupling) in problem.couplings.iter() {
+            ising.bonds[i][next[i]].weight = coupling;
+            ising.bonds[j][next[j]].weight = coupling;
+            next[i] += 1;
+            next[j] += 1;
         }
+        ising.fields = problem.fields.clone();
+        ising.temperature = 1.0;
+        ising
+    }
 
-        Self {
-            size,
-            lattice,
-            temperature: 2.696,
-            coupling_constant: 1.0,
-            magnetic_moment: 0.0,
-            magnetic_field_strength: 0.0,
+    /// Draws every spin independently, i.e. an infinite temperature configuration.
+    pub fn randomize<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let model = self.model;
+        for (x, &occupied) in self.lattice.iter_mut().zip(self.occupancy.iter()) {
+            *x = match model {
+                _ if !occupied => 0,
+                Model::Ising => 2 * (rng.gen::<f64>().round() as i8) - 1,
+                Model::Potts { states } => rng.gen_range(1..=states),
+                Model::BlumeCapel => rng.gen_range(-1..=1),
+            };
         }
     }
 
-    pub fn monte_carlo_step(&mut self, rng: &mut rand::rngs::ThreadRng) {
-        let pos = Vec2 {
-            x: rng.gen_range(0..self.size) as i32,
-            y: rng.gen_range(0..self.size) as i32,
+    /// Turns a random `fraction` of the sites into vacancies. Their spin 0 drops out of
+    /// every neighbour sum, and they are never updated. Former vacancies filled again
+    /// start up, while occupied sites keep their spin, Blume-Capel zeros included.
+    pub fn dilute<R: Rng + ?Sized>(&mut self, fraction: f64, rng: &mut R) {
+        for (x, occupied) in self.lattice.iter_mut().zip(self.occupancy.iter_mut()) {
+            let vacant = !*occupied;
+            *occupied = rng.gen::<f64>() >= fraction;
+            if !*occupied {
+                *x = 0;
+            } else if vacant {
+                *x = 1;
+            }
+        }
+    }
+
+    pub fn num_occupied(&self) -> usize {
+        self.occupancy.iter().filter(|&&occupied| occupied).count()
+    }
+
+    /// Whether every site is occupied and every bond has unit weight, as on the pure
+    /// lattice the exact solutions are of.
+    pub fn is_clean(&self) -> bool {
+        self.occupancy.iter().all(|&occupied| occupied) && self.bonds.iter().flatten().all(|bond| bond.weight == 1.0)
+    }
+
+    /// Mean spin per occupied site. For Potts spins, the order parameter
+    /// (q n_max / N - 1) / (q - 1) of the most populated state instead, which is 0 when the
+    /// states are equally populated and 1 when a single one is.
+    pub fn magnetization(&self) -> f64 {
+        let occupied = self.num_occupied() as f64;
+        match self.model {
+            Model::Ising | Model::BlumeCapel => self.lattice.iter().map(|&s| s as f64).sum::<f64>() / occupied,
+            Model::Potts { states } => {
+                let mut counts = vec![0usize; states as usize + 1];
+                self.lattice.iter().for_each(|&s| counts[s as usize] += 1);
+                let largest = counts[1..].iter().max().copied().unwrap_or(0) as f64;
+                let q = states as f64;
+                (q * largest / occupied - 1.0) / (q - 1.0)
+            }
+        }
+    }
+
+    /// Mean σ² per occupied site, the fraction of Blume-Capel spins out of the zero state.
+    pub fn quadrupole_moment(&self) -> f64 {
+        self.lattice.iter().map(|&s| (s * s) as f64).sum::<f64>() / self.num_occupied() as f64
+    }
+
+    /// Total energy per occupied site.
+    pub fn energy(&self) -> f64 {
+        self.total_energy() / self.num_occupied() as f64
+    }
+
+    /// Total energy of the configuration. Every bond is seen from both of its ends, hence
+    /// the half.
+    pub fn total_energy(&self) -> f64 {
+        (0..self.lattice.len())
+            .map(|idx| {
+                let spin = self.lattice[idx];
+                0.5 * self.bond_energy(idx, spin) + self.field_energy(idx, spin)
+            })
+            .sum()
+    }
+
+    /// Single spin Metropolis update of a random site, proposing the flipped spin, or
+    /// another Potts or Blume-Capel value drawn uniformly. Returns the change in energy.
+    pub fn monte_carlo_step<R: Rng + ?Sized>(&mut self, rng: &mut R) -> f64 {
+        let idx = rng.gen_range(0..self.lattice.len());
+        if !self.occupancy[idx] {
+            return 0.0;
+        }
+
+        let spin = self.lattice[idx];
+        let (new_spin, energy_diff) = match self.model {
+            Model::Ising => (-spin, -2.0 * self.hamiltonian(idx)),
+            _ => {
+                let new_spin = self.propose(spin, rng);
+                (new_spin, self.site_energy(idx, new_spin) - self.site_energy(idx, spin))
+            }
         };
+        if energy_diff <= 0.0 || rng.gen::<f64>() < f64::exp(-energy_diff / self.temperature) {
+            self.lattice[idx] = new_spin;
+            energy_diff
+        } else {
+            0.0
+        }
+    }
 
-        let energy_diff = -2.0 * self.hamiltonian(&pos);
-        if energy_diff <= 0.0 {
-            self.flip_spin_at_pos(&pos)
+    /// Spin value other than `spin`, drawn uniformly.
+    pub fn propose<R: Rng + ?Sized>(&self, spin: i8, rng: &mut R) -> i8 {
+        if self.model == Model::Ising {
+            return -spin;
+        }
+        let (lowest, highest) = self.spin_range();
+        let value = rng.gen_range(lowest..highest);
+        if value >= spin {
+            value + 1
         } else {
-            let p = f64::exp(-energy_diff / self.temperature);
-            if p <= 1.0 && rand::random::<f64>() < p {
-                self.flip_spin_at_pos(&pos);
+            value
+        }
+    }
+
+    /// Lowest and highest spin value. Ising spins skip the 0 in between.
+    fn spin_range(&self) -> (i8, i8) {
+        match self.model {
+            Model::Ising | Model::BlumeCapel => (-1, 1),
+            Model::Potts { states } => (1, states),
+        }
+    }
+
+    /// Zero temperature relaxation: moves spins to their lowest energy value until none
+    /// can lower the energy. Returns the change in energy.
+    pub fn descend(&mut self) -> f64 {
+        let mut total_diff = 0.0;
+        loop {
+            let mut flipped = false;
+            for idx in 0..self.lattice.len() {
+                let spin = self.lattice[idx];
+                let (lowest, highest) = self.spin_range();
+                let (new_spin, energy_diff) = match self.model {
+                    _ if !self.occupancy[idx] => (spin, 0.0),
+                    Model::Ising => (-spin, -2.0 * self.hamiltonian(idx)),
+                    _ => (lowest..=highest)
+                        .map(|value| (value, self.site_energy(idx, value) - self.site_energy(idx, spin)))
+                        .min_by(|a, b| a.1.total_cmp(&b.1))
+                        .unwrap_or((spin, 0.0)),
+                };
+                if energy_diff < 0.0 {
+                    self.lattice[idx] = new_spin;
+                    total_diff += energy_diff;
+                    flipped = true;
+                }
+            }
+            if !flipped {
+                return total_diff;
             }
         }
     }
 
-    fn periodic_bc(&self, index: i32) -> i32 {
-        index.rem_euclid(self.size as i32)
+    /// Zero temperature field sweep step: moves the uniform field in `direction` (±1) just
+    /// far enough to destabilize the weakest anti-aligned spin, then relaxes the avalanche
+    /// it triggers. Returns its size, or `None` once every spin points along `direction`.
+    /// The field is changed through `magnetic_field_strength`, so `magnetic_moment` must
+    /// be non-zero. Ising spins only.
+    pub fn next_avalanche(&mut self, direction: i8) -> Option<usize> {
+        let sign = direction as f64;
+        let (site, gap) = (0..self.lattice.len())
+            .filter(|&idx| self.lattice[idx] == -direction)
+            .map(|idx| (idx, -sign * self.effective_field(idx)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        self.magnetic_field_strength += sign * (gap.max(0.0) + 1e-9) / self.magnetic_moment;
+        Some(self.relax_from(site))
+    }
+
+    /// Flips unstable spins outward from `site` until the avalanche stops. Returns the
+    /// number of flips.
+    fn relax_from(&mut self, site: usize) -> usize {
+        let mut queue = VecDeque::from([site]);
+        let mut size = 0;
+        while let Some(idx) = queue.pop_front() {
+            if self.hamiltonian(idx) > 0.0 {
+                self.flip_spin(idx);
+                size += 1;
+                queue.extend(self.bonds[idx].iter().map(|bond| bond.site));
+            }
+        }
+        size
     }
 
-    fn get_index(&self, pos: &Vec2) -> usize {
-        (pos.y * (self.size as i32) + pos.x) as usize
+    /// Coupling constant of a kind of bond.
+    pub fn coupling(&self, kind: BondKind) -> f64 {
+        match kind {
+            BondKind::Horizontal => self.horizontal_coupling,
+            BondKind::Vertical => self.vertical_coupling,
+            BondKind::Diagonal => self.diagonal_coupling,
+            BondKind::Axial => self.axial_coupling,
+        }
     }
 
-    fn get_neighbors(&self, pos: &Vec2) -> [i8; 4] {
-        [
-            self.lattice[self.get_index(&Vec2 {
-                x: self.periodic_bc(pos.x + 1),
-                y: pos.y,
-            })],
-            self.lattice[self.get_index(&Vec2 {
-                x: self.periodic_bc(pos.x - 1),
-                y: pos.y,
-            })],
-            self.lattice[self.get_index(&Vec2 {
-                x: pos.x,
-                y: self.periodic_bc(pos.y + 1),
-            })],
-            self.lattice[self.get_index(&Vec2 {
-                x: pos.x,
-                y: self.periodic_bc(pos.y - 1),
-            })],
-        ]
+    /// Interaction of two spins, σ_i σ_j or δ(σ_i, σ_j), so that a bond contributes -J
+    /// times it to the energy. Vacancies interact with nothing.
+    pub fn pair(&self, a: i8, b: i8) -> f64 {
+        match self.model {
+            Model::Ising | Model::BlumeCapel => (a * b) as f64,
+            Model::Potts { .. } => (a == b && a != 0) as u8 as f64,
+        }
     }
 
-    fn hamiltonian(&self, pos: &Vec2) -> f64 {
-        let spin = self.lattice[self.get_index(pos)] as f64;
+    /// Energy of the bonds of a site if its spin were `spin`.
+    pub fn bond_energy(&self, idx: usize, spin: i8) -> f64 {
+        -self.bonds[idx]
+            .iter()
+            .map(|bond| self.coupling(bond.kind) * bond.weight * self.pair(spin, self.lattice[bond.site]))
+            .sum::<f64>()
+    }
 
-        let neighbors_spin_sum: f64 = self.get_neighbors(pos).iter().sum::<i8>() as f64;
-        let coupling_term: f64 = -self.coupling_constant * spin * neighbors_spin_sum;
-        let magnetic_term: f64 = -self.magnetic_moment * self.magnetic_field_strength * spin;
+    /// Energy of a site in the uniform, local, boundary wall and crystal fields if its spin
+    /// were `spin`.
+    pub fn field_energy(&self, idx: usize, spin: i8) -> f64 {
+        let wall_spin = |wall: &Wall| match self.model {
+            Model::Ising | Model::BlumeCapel => wall.spin as i8,
+            Model::Potts { .. } => if wall.spin > 0.0 { 1 } else { 2 },
+        };
+        let walls: f64 = self.walls[idx]
+            .iter()
+            .map(|wall| self.coupling(wall.kind) * self.pair(spin, wall_spin(wall)))
+            .sum();
+        let (favoured, crystal) = match self.model {
+            Model::Ising => (spin as f64, 0.0),
+            Model::Potts { .. } => ((spin == 1) as u8 as f64, 0.0),
+            Model::BlumeCapel => (spin as f64, self.crystal_field * (spin * spin) as f64),
+        };
+        -(self.magnetic_moment * self.magnetic_field_strength + self.fields[idx]) * favoured - walls + crystal
+    }
 
-        return coupling_term + magnetic_term;
+    /// Energy of a site with all its bonds and fields if its spin were `spin`.
+    pub fn site_energy(&self, idx: usize, spin: i8) -> f64 {
+        self.bond_energy(idx, spin) + self.field_energy(idx, spin)
     }
 
-    fn flip_spin_at_pos(&mut self, pos: &Vec2) {
-        let idx = self.get_index(pos);
-        self.lattice[idx] *= -1;
+    /// Sum of the neighbouring spins of a site, weighted by their couplings J_ij.
+    fn local_coupling(&self, idx: usize) -> f64 {
+        self.bonds[idx]
+            .iter()
+            .map(|bond| self.coupling(bond.kind) * bond.weight * self.lattice[bond.site] as f64)
+            .sum()
+    }
+
+    /// Uniform, local and boundary wall field acting on a site.
+    fn local_field(&self, idx: usize) -> f64 {
+        let walls: f64 = self.walls[idx].iter().map(|wall| self.coupling(wall.kind) * wall.spin).sum();
+        self.magnetic_moment * self.magnetic_field_strength + self.fields[idx] + walls
     }
 
-    pub fn lattice_as_braille(&self) -> String {
-        let width = self.size / 2;
-        let height = self.size / 4;
-        let num_pixels = width * height;
+    /// Total field acting on a site, from its neighbours and external fields.
+    fn effective_field(&self, idx: usize) -> f64 {
+        self.local_coupling(idx) + self.local_field(idx)
+    }
 
-        let chunk: Vec<&[i8]> = self.lattice.chunks(2).collect();
-        let chunk: Vec<Vec<&[i8]>> = chunk.chunks(width).map(|x| x.to_vec()).collect();
-        let chunk_t = transpose(chunk);
+    fn hamiltonian(&self, idx: usize) -> f64 {
+        -(self.lattice[idx] as f64) * self.effective_field(idx)
+    }
 
-        let mut i = 0;
-        let mut pixels_t = vec![[0; 8]; num_pixels];
+    fn flip_spin(&mut self, idx: usize) {
+        self.lattice[idx] *= -1;
+    }
 
-        for col in chunk_t {
-            let pixel_chunk = col.chunks(4);
-            for pixel in pixel_chunk {
-                let mut flat_pixel: [u8; 8] = [0; 8];
-                let mut j: usize = 0;
-                for b in pixel {
-                    flat_pixel[j] = ((b[0] + 1) / 2) as u8;
-                    flat_pixel[j + 1] = ((b[1] + 1) / 2) as u8;
-                    j += 2;
-                }
-                pixels_t[i] = flat_pixel;
-                i += 1;
-            }
+    /// Width and height of the drawing of one slice, in pixels.
+    pub fn canvas_size(&self) -> (usize, usize) {
+        match self.geometry.canvas_size() {
+            (0, 0) => (STRIP_WIDTH, self.lattice.len().div_ceil(STRIP_WIDTH)),
+            size => size,
         }
+    }
 
-        // TRANSPOSE PIXELS
-        let mut pixels = vec![[0; 8]; num_pixels];
-        for i in 0..width {
-            for j in 0..height {
-                pixels[i + j * width] = pixels_t[i * height + j];
+    /// Lays the sites of one slice out on a pixel grid according to the geometry. Two
+    /// dimensional lattices only have slice 0. Graphs without a layout fill a strip row by
+    /// row, from the highest to the lowest degree.
+    pub fn canvas(&self, slice: usize) -> Canvas {
+        let (width, height) = self.canvas_size();
+        let mut sites = vec![None; width * height];
+        if self.geometry.canvas_size() == (0, 0) {
+            let mut order: Vec<usize> = (0..self.lattice.len()).collect();
+            order.sort_by_key(|&idx| std::cmp::Reverse(self.bonds[idx].len()));
+            for (pixel, idx) in order.into_iter().enumerate() {
+                sites[pixel] = Some(idx);
+            }
+            return Canvas { width, height, sites };
+        }
+        for idx in 0..self.lattice.len() {
+            if let Some((pixel, s)) = self.geometry.pixel(idx) {
+                if s == slice {
+                    sites[pixel.y as usize * width + pixel.x as usize] = Some(idx);
+                }
             }
         }
+        Canvas { width, height, sites }
+    }
+
+    /// Draws up spins of one slice as braille dots, 2 x 4 pixels per character. Of Potts
+    /// spins, only state 1 is drawn.
+    pub fn lattice_as_braille(&self, slice: usize) -> String {
+        let canvas = self.canvas(slice);
+        let width = canvas.width.div_ceil(2);
+        let height = canvas.height.div_ceil(4);
 
-        // Convert to braille encoding
+        // Bit of each dot within a braille character, indexed by dy * 2 + dx
         let encoding = [0, 3, 1, 4, 2, 5, 6, 7];
-        let pixels: Vec<char> = pixels
-            .iter()
-            .map(|pixel| {
-                let mut pixel_braille: u8 = 0;
-                for (pixel_b, encoding_b) in pixel.iter().zip(encoding.iter()) {
-                    pixel_braille += pixel_b << encoding_b;
-                }
-                if let Some(result) = char::from_u32(10240 + pixel_braille as u32) {
-                    result
-                } else {
-                    '*'
-                }
-            })
-            .collect();
-
-        // Unflatten and convert to string
-        pixels.into_iter()
-            .enumerate()
-            .flat_map(|(i, c)| {
-                if i % width == 0 {
-                    Either::Left(['\n', c].into_iter())
-                } else {
-                    Either::Right(once(c))
-                }
-            })
-            .skip(1) // first Either::Left creates a leading \n so skip it before collecting
-            .collect::<String>()
+        let mut rows = Vec::with_capacity(height);
+        for row in 0..height {
+            let line: String = (0..width)
+                .map(|col| {
+                    let mut pixel_braille: u8 = 0;
+                    for (i, encoding_b) in encoding.iter().enumerate() {
+                        let (x, y) = (2 * col + i % 2, 4 * row + i / 2);
+                        if x >= canvas.width || y >= canvas.height {
+                            continue;
+                        }
+                        if let Some(idx) = canvas.sites[y * canvas.width + x] {
+                            if self.pair(self.lattice[idx], 1) > 0.0 {
+                                pixel_braille |= 1 << encoding_b;
+                            }
+                        }
+                    }
+                    char::from_u32(10240 + pixel_braille as u32).unwrap_or('*')
+                })
+                .collect();
+            rows.push(line);
+        }
+        rows.join("\n")
     }
 }
+
+/// Sites laid out for drawing: `sites[y * width + x]` is the site at pixel (x, y), if any.
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub sites: Vec<Option<usize>>,
+}
@@ -0,0 +1,367 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+use crate::geometry::{self, Geometry};
+use crate::ising::{Bond, BondKind, Wall};
+use crate::vector::Vec2;
+
+/// Sites and bonds the model runs on, with an optional layout for drawing.
+pub trait Lattice: Debug {
+    fn num_sites(&self) -> usize;
+
+    /// Bonds of `site`, with weight 1, or -1 across an antiperiodic seam.
+    fn neighbors(&self, site: usize) -> Vec<Bond>;
+
+    /// Fixed boundary spins bonded to `site`.
+    fn walls(&self, _site: usize) -> Vec<Wall> {
+        Vec::new()
+    }
+
+    /// Pixel a site is drawn at and the slice it belongs to, or None when the lattice has
+    /// no layout.
+    fn pixel(&self, _site: usize) -> Option<(Vec2, usize)> {
+        None
+    }
+
+    /// Width and height of one slice of the drawing, in pixels.
+    fn canvas_size(&self) -> (usize, usize) {
+        (0, 0)
+    }
+
+    /// Number of slices the drawing is cut into.
+    fn depth(&self) -> usize {
+        1
+    }
+
+    /// Critical temperature of the ferromagnet with the given couplings, if known.
+    fn critical_temperature(&self, _horizontal: f64, _vertical: f64) -> Option<f64> {
+        None
+    }
+
+    /// Critical temperature of the q-state Potts ferromagnet with the given couplings, if
+    /// known.
+    fn potts_critical_temperature(&self, _horizontal: f64, _vertical: f64, _states: usize) -> Option<f64> {
+        None
+    }
+
+    /// Whether `critical_temperature` is exact rather than numerical.
+    fn is_exactly_solved(&self) -> bool {
+        false
+    }
+
+    /// Width and height of a plain square lattice with periodic boundaries, whose finite
+    /// size partition function is known exactly.
+    fn square_torus(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Whether antiferromagnetic couplings frustrate the lattice.
+    fn is_frustrated(&self) -> bool {
+        false
+    }
+}
+
+/// One of the periodic `Geometry` grids with side length `size`.
+#[derive(Debug)]
+pub struct Grid {
+    pub geometry: Geometry,
+    pub size: usize,
+}
+
+impl Lattice for Grid {
+    fn num_sites(&self) -> usize {
+        self.geometry.num_sites(self.size)
+    }
+
+    fn neighbors(&self, site: usize) -> Vec<Bond> {
+        let pos = self.geometry.position(self.size, site);
+        self.geometry
+            .neighbors(&pos)
+            .iter()
+            .map(|neighbor| Bond {
+                site: self.geometry.index(self.size, neighbor),
+                weight: 1.0,
+                kind: if neighbor.y == pos.y && neighbor.z == pos.z {
+                    BondKind::Horizontal
+                } else {
+                    BondKind::Vertical
+                },
+            })
+            .collect()
+    }
+
+    fn pixel(&self, site: usize) -> Option<(Vec2, usize)> {
+        let pos = self.geometry.position(self.size, site);
+        Some((self.geometry.pixel(&pos), pos.z as usize))
+    }
+
+    fn canvas_size(&self) -> (usize, usize) {
+        self.geometry.canvas_size(self.size)
+    }
+
+    fn depth(&self) -> usize {
+        self.geometry.depth(self.size)
+    }
+
+    fn critical_temperature(&self, horizontal: f64, vertical: f64) -> Option<f64> {
+        match self.geometry {
+            Geometry::Square => Some(geometry::square_critical_temperature(horizontal, vertical)),
+            _ if horizontal == vertical => Some(horizontal.abs() * self.geometry.critical_temperature()),
+            _ => None,
+        }
+    }
+
+    fn potts_critical_temperature(&self, horizontal: f64, vertical: f64, states: usize) -> Option<f64> {
+        let ferromagnetic = horizontal > 0.0 && vertical > 0.0;
+        (self.geometry == Geometry::Square && ferromagnetic)
+            .then(|| geometry::square_potts_critical_temperature(horizontal, vertical, states))
+    }
+
+    fn is_exactly_solved(&self) -> bool {
+        self.geometry.is_exactly_solved()
+    }
+
+    fn is_frustrated(&self) -> bool {
+        self.geometry.is_frustrated()
+    }
+
+    fn square_torus(&self) -> Option<(usize, usize)> {
+        (self.geometry == Geometry::Square).then_some((self.size, self.size))
+    }
+}
+
+/// What a bond leaving the lattice across one edge connects to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary {
+    Periodic,
+    /// Free edges, with no bond.
+    Open,
+    /// Periodic with ferro- and antiferromagnetic bonds swapped across the seam, which
+    /// forces a domain wall into the ordered phase.
+    Antiperiodic,
+    /// Periodic, with each wrap moving one row or column on, so the sites form a single
+    /// helix.
+    Helical,
+    /// Walls of spins fixed up before the first row or column and down after the last.
+    Fixed,
+}
+
+impl Boundary {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "periodic" => Ok(Boundary::Periodic),
+            "open" => Ok(Boundary::Open),
+            "antiperiodic" => Ok(Boundary::Antiperiodic),
+            "helical" => Ok(Boundary::Helical),
+            "fixed" => Ok(Boundary::Fixed),
+            _ => Err(format!("unrecognized boundary condition '{}'", name)),
+        }
+    }
+}
+
+/// Square lattice of `width` x `height` sites with a boundary condition along x and y,
+/// optionally with competing further neighbour bonds.
+#[derive(Debug)]
+pub struct Rectangular {
+    pub width: usize,
+    pub height: usize,
+    pub boundaries: [Boundary; 2],
+    /// Second neighbour bonds across both diagonals.
+    pub diagonal: bool,
+    /// Third neighbour bonds two sites apart along x.
+    pub axial: bool,
+}
+
+impl Rectangular {
+    pub fn square(size: usize) -> Self {
+        Self {
+            width: size,
+            height: size,
+            boundaries: [Boundary::Periodic; 2],
+            diagonal: false,
+            axial: false,
+        }
+    }
+
+    /// Bond to the site (dx, dy) away from (x, y), or to the wall there. Crossing an edge
+    /// applies its boundary condition. None across an open edge.
+    fn step(&self, x: i32, y: i32, (dx, dy): (i32, i32), kind: BondKind) -> Option<Result<Bond, Wall>> {
+        let sizes = [self.width as i32, self.height as i32];
+        let mut pos = [x + dx, y + dy];
+        let mut weight = 1.0;
+        for axis in 0..2 {
+            if (0..sizes[axis]).contains(&pos[axis]) {
+                continue;
+            }
+            let side = if pos[axis] < 0 { -1 } else { 1 };
+            match self.boundaries[axis] {
+                Boundary::Periodic => {}
+                Boundary::Antiperiodic => weight = -weight,
+                Boundary::Helical => pos[1 - axis] += side,
+                Boundary::Open => return None,
+                Boundary::Fixed => {
+                    let spin = -side as f64;
+                    return Some(Err(Wall { spin, kind }));
+                }
+            }
+        }
+        let site = self.index(pos[0], pos[1]);
+        Some(Ok(Bond { site, weight, kind }))
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        let x = x.rem_euclid(self.width as i32) as usize;
+        let y = y.rem_euclid(self.height as i32) as usize;
+        y * self.width + x
+    }
+
+    fn steps(&self, site: usize) -> impl Iterator<Item = Result<Bond, Wall>> + '_ {
+        let (x, y) = ((site % self.width) as i32, (site / self.width) as i32);
+        let mut offsets = vec![
+            ((1, 0), BondKind::Horizontal),
+            ((-1, 0), BondKind::Horizontal),
+            ((0, 1), BondKind::Vertical),
+            ((0, -1), BondKind::Vertical),
+        ];
+        if self.diagonal {
+            offsets.extend([(1, 1), (-1, -1), (1, -1), (-1, 1)].map(|offset| (offset, BondKind::Diagonal)));
+        }
+        if self.axial {
+            offsets.extend([(2, 0), (-2, 0)].map(|offset| (offset, BondKind::Axial)));
+        }
+        offsets
+            .into_iter()
+            .filter_map(move |(offset, kind)| self.step(x, y, offset, kind))
+    }
+}
+
+impl Lattice for Rectangular {
+    fn num_sites(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn neighbors(&self, site: usize) -> Vec<Bond> {
+        self.steps(site).filter_map(Result::ok).collect()
+    }
+
+    fn walls(&self, site: usize) -> Vec<Wall> {
+        self.steps(site).filter_map(Result::err).collect()
+    }
+
+    fn pixel(&self, site: usize) -> Option<(Vec2, usize)> {
+        let pos = Vec2 {
+            x: (site % self.width) as i32,
+            y: (site / self.width) as i32,
+        };
+        Some((pos, 0))
+    }
+
+    fn canvas_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// The thermodynamic limit depends neither on the aspect ratio nor on the boundaries,
+    /// but competing further neighbour bonds leave it unsolved.
+    fn critical_temperature(&self, horizontal: f64, vertical: f64) -> Option<f64> {
+        if self.diagonal || self.axial {
+            return None;
+        }
+        Some(geometry::square_critical_temperature(horizontal, vertical))
+    }
+
+    fn potts_critical_temperature(&self, horizontal: f64, vertical: f64, states: usize) -> Option<f64> {
+        let ferromagnetic = horizontal > 0.0 && vertical > 0.0;
+        (!self.diagonal && !self.axial && ferromagnetic)
+            .then(|| geometry::square_potts_critical_temperature(horizontal, vertical, states))
+    }
+
+    fn is_exactly_solved(&self) -> bool {
+        !self.diagonal && !self.axial
+    }
+
+    fn square_torus(&self) -> Option<(usize, usize)> {
+        let periodic = self.boundaries == [Boundary::Periodic; 2];
+        (periodic && !self.diagonal && !self.axial).then_some((self.width, self.height))
+    }
+}
+
+/// Arbitrary graph given by its adjacency lists. It has no layout, so it is drawn as a
+/// strip of sites sorted by degree.
+#[derive(Debug)]
+pub struct Graph {
+    adjacency: Vec<Vec<usize>>,
+    bipartite: bool,
+    /// Known for ensembles such as random regular graphs.
+    pub critical_temperature: Option<f64>,
+}
+
+impl Graph {
+    /// Graph of `num_sites` vertices with an undirected bond per edge.
+    pub fn from_edges(num_sites: usize, edges: &[(usize, usize)]) -> Self {
+        let mut adjacency = vec![Vec::new(); num_sites];
+        for &(i, j) in edges {
+            adjacency[i].push(j);
+            adjacency[j].push(i);
+        }
+        let bipartite = is_bipartite(&adjacency);
+        Self {
+            adjacency,
+            bipartite,
+            critical_temperature: None,
+        }
+    }
+}
+
+/// Two-colours a graph breadth first, which fails on an odd cycle.
+fn is_bipartite(adjacency: &[Vec<usize>]) -> bool {
+    let mut color = vec![0i8; adjacency.len()];
+    let mut queue = VecDeque::new();
+    for root in 0..adjacency.len() {
+        if color[root] != 0 {
+            continue;
+        }
+        color[root] = 1;
+        queue.push_back(root);
+        while let Some(i) = queue.pop_front() {
+            for &j in adjacency[i].iter() {
+                if color[j] == 0 {
+                    color[j] = -color[i];
+                    queue.push_back(j);
+                } else if color[j] == color[i] {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+impl Lattice for Graph {
+    fn num_sites(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    /// Graphs have no directions, so every bond is horizontal.
+    fn neighbors(&self, site: usize) -> Vec<Bond> {
+        self.adjacency[site]
+            .iter()
+            .map(|&site| Bond {
+                site,
+                weight: 1.0,
+                kind: BondKind::Horizontal,
+            })
+            .collect()
+    }
+
+    fn critical_temperature(&self, horizontal: f64, _vertical: f64) -> Option<f64> {
+        self.critical_temperature.map(|t| horizontal.abs() * t)
+    }
+
+    fn is_exactly_solved(&self) -> bool {
+        self.critical_temperature.is_some()
+    }
+
+    fn is_frustrated(&self) -> bool {
+        !self.bipartite
+    }
+}
@@ -1,10 +1,40 @@
+mod anneal;
 mod app;
+mod avalanche;
+mod cli;
+mod cluster;
+mod disorder;
+mod exact;
+mod geometry;
+mod heisenberg;
+mod interface;
 mod ui;
 mod ising;
+mod lattice;
+mod multicanonical;
+mod network;
+mod onsager;
 mod parameter;
+mod planar;
+mod potts;
+mod problem;
+mod reweighting;
+mod schedule;
+mod structure;
+mod tempering;
 mod vector;
+mod wang_landau;
 
 use app::App;
+use cli::{Command, Method, Options, USAGE};
+use exact::{Enumeration, TransferMatrix};
+use geometry::Geometry;
+use ising::{Ising, Model};
+use multicanonical::{Coordinate, Weights};
+use onsager::Torus;
+use problem::Problem;
+use reweighting::Reweighting;
+use tempering::ParallelTempering;
 
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -16,6 +46,234 @@ use ratatui::{backend::CrosstermBackend, Terminal};
 use std::{error::Error, io};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    if options.help {
+        print!("{}", USAGE);
+        return Ok(());
+    }
+
+    if let Command::Solve(path) = &options.command {
+        let mut rng = options.rng();
+        let problem = Problem::from_file(path, options.format)?;
+        let mut ising = Ising::from_problem(&problem, &mut rng);
+        let annealer = &options.annealer;
+        let solution = match options.method {
+            Method::Anneal => annealer.run(&mut ising, &mut rng),
+            Method::Tempering => {
+                let ladder = ParallelTempering::geometric_ladder(annealer.t_end, annealer.t_start, options.replicas);
+                ParallelTempering::new(&ising, ladder, &mut rng).solve(annealer.sweeps, &mut rng)
+            }
+        };
+        println!("{}", problem.interpret(&solution));
+        return Ok(());
+    }
+
+    if let Command::Interface = options.command {
+        let mut rng = options.rng();
+        println!("{:>8} {:>10} {:>10}", "T", "sigma", "exact");
+        for &temperature in options.temperatures.iter() {
+            let tension = options.integration.run(options.size, 1.0, temperature, &mut rng);
+            let exact = interface::exact_tension(1.0, temperature);
+            println!("{:>8.4} {:>10.4} {:>10.4}", temperature, tension, exact);
+        }
+        return Ok(());
+    }
+
+    if let Command::Potts = options.command {
+        let mut rng = options.rng();
+        let models = options.comparison.models();
+        let results = options.comparison.run(options.size, &options.temperatures, &mut rng);
+        print!("{:>8}", "T/T_c");
+        for model in models.iter() {
+            // The comparison runs the Ising model and Potts models only
+            let name = match model {
+                Model::Potts { states } => format!("q={}", states),
+                _ => "ising".to_string(),
+            };
+            print!(" {:>10} {:>10}", format!("m({})", name), format!("E({})", name));
+            if *model == Model::Ising {
+                print!(" {:>10} {:>10}", "m(exact)", "E(exact)");
+            }
+        }
+        println!();
+        let critical_temperature = Geometry::Square.critical_temperature();
+        for (k, &temperature) in options.temperatures.iter().enumerate() {
+            print!("{:>8.4}", temperature);
+            for (model, measurements) in models.iter().zip(results.iter()) {
+                let (magnetization, energy) = measurements[k];
+                print!(" {:>10.4} {:>10.4}", magnetization, energy);
+                // Onsager and Yang's infinite lattice results
+                if *model == Model::Ising {
+                    let t = temperature * critical_temperature;
+                    print!(" {:>10.4} {:>10.4}", onsager::spontaneous_magnetization(1.0, 1.0, t), onsager::energy(1.0, t));
+                }
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    if let Command::Exact = options.command {
+        if let Some(width) = options.strip {
+            let transfer_matrix = TransferMatrix::new(width, 1.0, 1.0)?;
+            println!("{:>8} {:>10} {:>10} {:>10} {:>10} {:>10}", "T", "f", "E", "m", "C", "chi");
+            for &temperature in options.temperatures.iter() {
+                let exact = transfer_matrix.thermodynamics(temperature, options.field);
+                println!(
+                    "{:>8.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                    temperature, exact.free_energy, exact.energy, exact.magnetization, exact.specific_heat, exact.susceptibility
+                );
+            }
+            return Ok(());
+        }
+
+        let mut rng = options.rng();
+        let mut ising = options.ising(&mut rng)?;
+        let enumeration = Enumeration::new(&ising)?;
+        let estimates = options.validation.run(&mut ising, &options.temperatures, options.field, &mut rng);
+        print!("{:>8}", "T");
+        for name in ["E", "|m|", "C", "chi"] {
+            print!(" {:>10} {:>10}", name, format!("{}(mc)", name));
+        }
+        println!();
+        for (&temperature, estimate) in options.temperatures.iter().zip(estimates.iter()) {
+            let exact = enumeration.thermodynamics(temperature, options.field);
+            print!("{:>8.4}", temperature);
+            for (value, mc) in [
+                (exact.energy, estimate.energy),
+                (exact.absolute_magnetization.unwrap_or(f64::NAN), estimate.absolute_magnetization.unwrap_or(f64::NAN)),
+                (exact.specific_heat, estimate.specific_heat),
+                (exact.susceptibility, estimate.susceptibility),
+            ] {
+                print!(" {:>10.4} {:>10.4}", value, mc);
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    if let Command::WangLandau = options.command {
+        let mut rng = options.rng();
+        let ising = options.ising(&mut rng)?;
+        let density_of_states = options.wang_landau.run(&ising, &mut rng);
+        if let Some(path) = &options.output {
+            density_of_states.write(path)?;
+        }
+        println!(
+            "# {} levels, {} sweeps, ln f = {:e}",
+            density_of_states.ln_g().len(),
+            density_of_states.sweeps(),
+            density_of_states.ln_f()
+        );
+        println!("{:>8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}", "T", "f", "E", "S", "|m|", "C", "chi");
+        for &temperature in options.temperatures.iter() {
+            let estimate = density_of_states.thermodynamics(temperature);
+            let entropy = (estimate.energy - estimate.free_energy) / temperature;
+            println!(
+                "{:>8.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                temperature,
+                estimate.free_energy,
+                estimate.energy,
+                entropy,
+                estimate.absolute_magnetization.unwrap_or(f64::NAN),
+                estimate.specific_heat,
+                estimate.susceptibility
+            );
+        }
+        return Ok(());
+    }
+
+    if let Command::Multicanonical = options.command {
+        let mut rng = options.rng();
+        let mut ising = options.ising(&mut rng)?;
+        let multicanonical = &options.multicanonical;
+        let weights = match &options.weights {
+            Some(path) => Some(Weights::load(path, multicanonical.coordinate, multicanonical.bin_width)?),
+            None => None,
+        };
+        // Two interfaces span the shorter side of the torus when its halves order oppositely
+        let interfaces = match (multicanonical.coordinate, Torus::of(&ising)) {
+            (Coordinate::Magnetization, Some(torus)) => Some(2.0 * torus.width.min(torus.height) as f64),
+            _ => None,
+        };
+        println!(
+            "{:>8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "T", "E", "|m|", "C", "chi", "dF/T", "sigma(L)", "sigma"
+        );
+        for &temperature in options.temperatures.iter() {
+            ising.temperature = temperature;
+            let ensemble = multicanonical.run(&mut ising, weights.clone(), &mut rng)?;
+            if let Some(path) = &options.output {
+                ensemble.write(path)?;
+            }
+            let estimate = ensemble.thermodynamics();
+            let barrier = ensemble.barrier();
+            let (tension, exact_tension) = match interfaces {
+                Some(length) => (temperature * barrier / length, interface::exact_tension(1.0, temperature)),
+                None => (f64::NAN, f64::NAN),
+            };
+            println!(
+                "{:>8.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                temperature,
+                estimate.energy,
+                estimate.absolute_magnetization.unwrap_or(f64::NAN),
+                estimate.specific_heat,
+                estimate.susceptibility,
+                barrier,
+                tension,
+                exact_tension
+            );
+        }
+        return Ok(());
+    }
+
+    if let Command::Reweight = options.command {
+        let (sites, series) = match &options.series {
+            Some(path) => reweighting::load(path)?,
+            None => {
+                let mut rng = options.rng();
+                let mut ising = options.ising(&mut rng)?;
+                let series = options.recording.run(&mut ising, &options.runs, &mut rng);
+                if let Some(path) = &options.output {
+                    reweighting::write(path, ising.num_occupied(), &series)?;
+                }
+                (ising.num_occupied(), series)
+            }
+        };
+        let reweighting = Reweighting::new(sites, &series)?;
+        let (runs, samples) = reweighting.samples();
+        let (lowest, highest) = reweighting.range();
+        println!(
+            "# {} series, {} samples, reaching T = {:.4} to {:.4}, h = {}",
+            runs, samples, lowest, highest, options.field
+        );
+        let temperatures = match options.temperatures.is_empty() {
+            true => (0..13).map(|k| lowest + (highest - lowest) * k as f64 / 12.0).collect(),
+            false => options.temperatures.clone(),
+        };
+        println!("{:>8} {:>10} {:>10} {:>10} {:>10} {:>10}", "T", "E", "m", "|m|", "C", "chi");
+        for &temperature in temperatures.iter() {
+            let estimate = reweighting.thermodynamics(temperature, options.field);
+            println!(
+                "{:>8.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                temperature,
+                estimate.energy,
+                estimate.magnetization,
+                estimate.absolute_magnetization.unwrap_or(f64::NAN),
+                estimate.specific_heat,
+                estimate.susceptibility
+            );
+        }
+        let (temperature, specific_heat) = reweighting.maximum(options.field, |estimate| estimate.specific_heat);
+        println!("# C peak:   T = {:.5}, C = {:.4}", temperature, specific_heat);
+        let (temperature, susceptibility) = reweighting.maximum(options.field, |estimate| estimate.susceptibility);
+        println!("# chi peak: T = {:.5}, chi = {:.4}", temperature, susceptibility);
+        return Ok(());
+    }
+
+    // Built before the terminal is taken over, to report errors normally
+    let mut app = App::new(options)?;
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -24,7 +282,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut terminal = Terminal::new(backend)?;
 
     // Logic
-    let mut app = App::new(200);
     app.run(&mut terminal)?;
 
     // Restore terminal
@@ -0,0 +1,352 @@
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::exact::{self, Thermodynamics};
+use crate::ising::{Ising, Model};
+use crate::wang_landau::LEVEL_RESOLUTION;
+
+/// Quantity the sampling is flattened along.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinate {
+    Energy,
+    /// Total magnetization Σσ, of Ising or Blume-Capel spins.
+    Magnetization,
+}
+
+impl Coordinate {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "energy" => Ok(Self::Energy),
+            "magnetization" => Ok(Self::Magnetization),
+            _ => Err(format!("unrecognized umbrella coordinate '{}'", name)),
+        }
+    }
+}
+
+/// Bias ln W(x) over bins of the coordinate, under which configurations are sampled with
+/// probability ∝ W(x) exp(-E / T).
+#[derive(Debug, Clone)]
+pub struct Weights {
+    pub coordinate: Coordinate,
+    /// Width of the energy bins, or 0 for one bin per distinct energy.
+    pub bin_width: f64,
+    ln_w: BTreeMap<i64, f64>,
+}
+
+impl Weights {
+    pub fn new(coordinate: Coordinate, bin_width: f64) -> Self {
+        Self {
+            coordinate,
+            bin_width,
+            ln_w: BTreeMap::new(),
+        }
+    }
+
+    /// Reads 'x ln_W(x)' lines, as written by [`Ensemble::write`], ignoring further columns.
+    pub fn load(path: &str, coordinate: Coordinate, bin_width: f64) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut weights = Self::new(coordinate, bin_width);
+        for (number, line) in contents.lines().enumerate() {
+            let fields: Vec<f64> = line
+                .split_whitespace()
+                .take(2)
+                .map(|field| field.parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("{}:{}: expected 'x ln_W(x)', got '{}'", path, number + 1, line))?;
+            match fields.as_slice() {
+                [] => {}
+                [x, ln_w] => {
+                    let key = weights.key(*x);
+                    weights.ln_w.insert(key, *ln_w);
+                }
+                _ => return Err(format!("{}:{}: expected 'x ln_W(x)', got '{}'", path, number + 1, line)),
+            }
+        }
+        Ok(weights)
+    }
+
+    fn width(&self) -> f64 {
+        match self.coordinate {
+            Coordinate::Magnetization => 1.0,
+            Coordinate::Energy if self.bin_width > 0.0 => self.bin_width,
+            Coordinate::Energy => LEVEL_RESOLUTION,
+        }
+    }
+
+    fn key(&self, x: f64) -> i64 {
+        (x / self.width()).round() as i64
+    }
+
+    /// ln W of a bin, where a bin never weighted gets the largest weight so far, to draw
+    /// the walk there.
+    fn ln_w(&mut self, key: i64) -> f64 {
+        if let Some(&ln_w) = self.ln_w.get(&key) {
+            return ln_w;
+        }
+        let largest = self.ln_w.values().copied().fold(f64::NEG_INFINITY, f64::max);
+        let ln_w = if largest.is_finite() { largest } else { 0.0 };
+        self.ln_w.insert(key, ln_w);
+        ln_w
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bin {
+    samples: u64,
+    /// Sums of E, E², M, |M| and M² over the samples.
+    sums: [f64; 5],
+}
+
+/// Multicanonical or umbrella sampling: single spin Metropolis moves accepted with
+/// min(1, W(x') exp(-ΔE / T) / W(x)). The weights are first adapted by a Wang-Landau walk
+/// in x, lowering ln W(x) by ln f at every visit and halving ln f whenever the histogram
+/// of visits is flat, so the walk crosses freely between the states a barrier separates
+/// at the temperature of `ising`. The samples drawn with the final weights are then
+/// reweighted by 1 / W(x), which is exact for any weights.
+#[derive(Debug)]
+pub struct Multicanonical {
+    pub coordinate: Coordinate,
+    /// Halvings of ln f from 1 before the weights are kept fixed.
+    pub iterations: usize,
+    /// Fraction of the mean number of visits every bin needs for the histogram to count as
+    /// flat.
+    pub flatness: f64,
+    /// Sweeps sampled with the final weights.
+    pub sweeps: usize,
+    pub bin_width: f64,
+}
+
+impl Default for Multicanonical {
+    fn default() -> Self {
+        Self {
+            coordinate: Coordinate::Magnetization,
+            iterations: 10,
+            flatness: 0.8,
+            sweeps: 50000,
+            bin_width: 0.0,
+        }
+    }
+}
+
+/// Energy and magnetization of the configuration walked, kept up to date move by move.
+struct Walk {
+    energy: f64,
+    magnetization: i64,
+}
+
+impl Walk {
+    fn new(ising: &Ising) -> Self {
+        Self {
+            energy: ising.total_energy(),
+            magnetization: ising.lattice.iter().map(|&s| s as i64).sum(),
+        }
+    }
+
+    fn coordinate(&self, coordinate: Coordinate) -> f64 {
+        match coordinate {
+            Coordinate::Energy => self.energy,
+            Coordinate::Magnetization => self.magnetization as f64,
+        }
+    }
+
+    /// One move attempted at a random site. Returns the bin the walk is in afterwards and
+    /// the bin proposed.
+    fn attempt<R: Rng + ?Sized>(&mut self, ising: &mut Ising, weights: &mut Weights, rng: &mut R) -> (i64, i64) {
+        let idx = rng.gen_range(0..ising.lattice.len());
+        let key = weights.key(self.coordinate(weights.coordinate));
+        // Weighs the bin before any return, since the walk may start at a vacancy
+        let ln_w = weights.ln_w(key);
+        if !ising.occupancy[idx] {
+            return (key, key);
+        }
+        let spin = ising.lattice[idx];
+        let new_spin = ising.propose(spin, rng);
+        let proposed = Self {
+            energy: self.energy + ising.site_energy(idx, new_spin) - ising.site_energy(idx, spin),
+            magnetization: self.magnetization + (new_spin - spin) as i64,
+        };
+        let new_key = weights.key(proposed.coordinate(weights.coordinate));
+        let exponent = weights.ln_w(new_key) - ln_w - (proposed.energy - self.energy) / ising.temperature;
+        if exponent >= 0.0 || rng.gen::<f64>() < exponent.exp() {
+            ising.lattice[idx] = new_spin;
+            *self = proposed;
+            (new_key, new_key)
+        } else {
+            (key, new_key)
+        }
+    }
+}
+
+impl Multicanonical {
+    /// Adapts `weights`, or flat ones without, then samples with them.
+    pub fn run<R: Rng + ?Sized>(&self, ising: &mut Ising, weights: Option<Weights>, rng: &mut R) -> Result<Ensemble, String> {
+        if self.coordinate == Coordinate::Magnetization && matches!(ising.model, Model::Potts { .. }) {
+            return Err("the magnetization coordinate needs Ising or Blume-Capel spins".to_string());
+        }
+        let mut weights = weights.unwrap_or_else(|| Weights::new(self.coordinate, self.bin_width));
+        ising.randomize(rng);
+        self.adapt(ising, &mut weights, rng);
+        Ok(self.sample(ising, weights, rng))
+    }
+
+    fn adapt<R: Rng + ?Sized>(&self, ising: &mut Ising, weights: &mut Weights, rng: &mut R) {
+        let mut walk = Walk::new(ising);
+        // Visits since the last halving of every bin proposed so far, which keeps the
+        // histogram from counting as flat before the walk has been everywhere it can go
+        let mut visits: BTreeMap<i64, u64> = BTreeMap::new();
+        let mut ln_f = 1.0;
+        for _ in 0..self.iterations {
+            loop {
+                for _ in 0..ising.lattice.len() {
+                    let (key, proposed) = walk.attempt(ising, weights, rng);
+                    visits.entry(proposed).or_default();
+                    *weights.ln_w.get_mut(&key).expect("weighted when entered") -= ln_f;
+                    *visits.entry(key).or_default() += 1;
+                }
+                let lowest = visits.values().copied().min().unwrap_or(0) as f64;
+                let mean = visits.values().sum::<u64>() as f64 / visits.len() as f64;
+                if lowest >= self.flatness * mean {
+                    break;
+                }
+            }
+            ln_f /= 2.0;
+            visits.values_mut().for_each(|count| *count = 0);
+        }
+        let largest = weights.ln_w.values().copied().fold(f64::NEG_INFINITY, f64::max);
+        weights.ln_w.values_mut().for_each(|ln_w| *ln_w -= largest);
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, ising: &mut Ising, mut weights: Weights, rng: &mut R) -> Ensemble {
+        let mut walk = Walk::new(ising);
+        let mut bins: BTreeMap<i64, Bin> = BTreeMap::new();
+        for _ in 0..self.sweeps * ising.lattice.len() {
+            let (key, _) = walk.attempt(ising, &mut weights, rng);
+            let (energy, m) = (walk.energy, walk.magnetization as f64);
+            let bin = bins.entry(key).or_default();
+            bin.samples += 1;
+            for (sum, value) in bin.sums.iter_mut().zip([energy, energy * energy, m, m.abs(), m * m]) {
+                *sum += value;
+            }
+        }
+        Ensemble {
+            weights,
+            bins,
+            sites: ising.num_occupied(),
+            temperature: ising.temperature,
+            model: ising.model,
+        }
+    }
+}
+
+/// Samples of a multicanonical run, binned along its coordinate.
+#[derive(Debug)]
+pub struct Ensemble {
+    pub weights: Weights,
+    bins: BTreeMap<i64, Bin>,
+    sites: usize,
+    temperature: f64,
+    model: Model,
+}
+
+impl Ensemble {
+    /// Samples over bins, each reweighted by 1 / W, shifted for the largest to be 1.
+    fn reweighted(&self) -> impl Iterator<Item = (i64, &Bin, f64)> {
+        let ln_w = |key: &i64| self.weights.ln_w.get(key).copied().unwrap_or(0.0);
+        let lowest = self.bins.keys().map(ln_w).fold(f64::INFINITY, f64::min);
+        self.bins.iter().map(move |(key, bin)| (*key, bin, (lowest - ln_w(key)).exp()))
+    }
+
+    /// Canonical averages per site at the temperature sampled, with no free energy.
+    /// Magnetizations are NaN for Potts spins.
+    pub fn thermodynamics(&self) -> Thermodynamics {
+        let (mut z, mut sums) = (0.0, [0.0; 5]);
+        for (_, bin, weight) in self.reweighted() {
+            z += weight * bin.samples as f64;
+            for (sum, value) in sums.iter_mut().zip(bin.sums) {
+                *sum += weight * value;
+            }
+        }
+        let [e, e2, m, abs_m, m2] = sums.map(|sum| sum / z);
+        let n = self.sites as f64;
+        let temperature = self.temperature;
+        let potts = matches!(self.model, Model::Potts { .. });
+        Thermodynamics {
+            free_energy: f64::NAN,
+            energy: e / n,
+            magnetization: if potts { f64::NAN } else { m / n },
+            absolute_magnetization: (!potts).then_some(abs_m / n),
+            specific_heat: exact::fluctuation(e2 - e * e, n * temperature * temperature),
+            susceptibility: if potts { f64::NAN } else { exact::fluctuation(m2 - abs_m * abs_m, n * temperature) },
+        }
+    }
+
+    /// Canonical free energy profile F(x) / T = -ln P(x) of every bin sampled, lowest x
+    /// first with x per site, shifted to be 0 at its minimum.
+    pub fn profile(&self) -> Vec<(f64, f64)> {
+        let width = self.weights.width();
+        let ln_p: Vec<(f64, f64)> = self
+            .reweighted()
+            .map(|(key, bin, weight)| (key as f64 * width / self.sites as f64, (weight * bin.samples as f64).ln()))
+            .collect();
+        let largest = ln_p.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        ln_p.into_iter().map(|(x, ln_p)| (x, largest - ln_p)).collect()
+    }
+
+    /// Barrier ΔF / T between the two deepest minima of the profile: the largest rise, over
+    /// every bin, from it to the lower of the deepest minima on either side. 0 with a
+    /// single minimum.
+    pub fn barrier(&self) -> f64 {
+        let profile: Vec<f64> = self.profile().into_iter().map(|p| p.1).collect();
+        let mut left = vec![f64::INFINITY; profile.len()];
+        let mut right = vec![f64::INFINITY; profile.len()];
+        for i in 1..profile.len() {
+            left[i] = left[i - 1].min(profile[i - 1]);
+            let j = profile.len() - 1 - i;
+            right[j] = right[j + 1].min(profile[j + 1]);
+        }
+        (0..profile.len())
+            .map(|i| profile[i] - left[i].max(right[i]))
+            .filter(|rise| rise.is_finite())
+            .fold(0.0, f64::max)
+    }
+
+    /// Writes 'x ln_W(x) ln_P(x)' lines of the bins sampled, with x the total energy or
+    /// magnetization, which [`Weights::load`] reads back.
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let width = self.weights.width();
+        let lines: String = self
+            .reweighted()
+            .map(|(key, bin, weight)| {
+                let ln_w = self.weights.ln_w.get(&key).copied().unwrap_or(0.0);
+                format!("{} {} {}\n", key as f64 * width, ln_w, (weight * bin.samples as f64).ln())
+            })
+            .collect();
+        fs::write(path, lines).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lattice::Rectangular;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::rc::Rc;
+
+    #[test]
+    fn adapts_on_diluted_lattices() {
+        for seed in [1, 5] {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut ising = Ising::new(Rc::new(Rectangular::square(8)), &mut rng);
+            ising.dilute(0.5, &mut rng);
+            ising.temperature = 2.0;
+            let multicanonical = Multicanonical {
+                iterations: 2,
+                sweeps: 100,
+                ..Multicanonical::default()
+            };
+            let ensemble = multicanonical.run(&mut ising, None, &mut rng).expect("Ising spins");
+            assert!(ensemble.thermodynamics().energy.is_finite());
+        }
+    }
+}
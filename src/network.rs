@@ -0,0 +1,308 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::rc::Rc;
+
+use crate::lattice::{Graph, Lattice};
+
+/// Restarts of the random regular pairing before giving up on avoiding multi-edges.
+const PAIRING_ATTEMPTS: usize = 100;
+
+/// A graph to run the model on, read from a file or drawn from a random ensemble.
+#[derive(Debug)]
+pub enum Network {
+    Loaded(Rc<Graph>),
+    /// Every site has `degree` neighbours, otherwise uniformly random.
+    RandomRegular { sites: usize, degree: usize },
+    /// Watts-Strogatz: a ring with `degree` nearest neighbours, each bond rewired with
+    /// probability `rewiring`.
+    SmallWorld { sites: usize, degree: usize, rewiring: f64 },
+    /// Barabási-Albert: sites attach `links` bonds each, preferentially to high degree sites.
+    ScaleFree { sites: usize, links: usize },
+}
+
+impl Network {
+    /// Parses a graph spec of the form `edges:path`, `adjacency:path`, `graphml:path`,
+    /// `regular:n:k`, `small-world:n:k:p` or `scale-free:n:m`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, rest) = spec.split_once(':').unwrap_or((spec, ""));
+        match kind {
+            "edges" => return Ok(Network::Loaded(Rc::new(from_edge_list(rest)?))),
+            "adjacency" => return Ok(Network::Loaded(Rc::new(from_adjacency_list(rest)?))),
+            "graphml" => return Ok(Network::Loaded(Rc::new(from_graphml(rest)?))),
+            _ => {}
+        }
+
+        let fields: Vec<&str> = rest.split(':').collect();
+        let count = |v: &str| v.parse::<usize>().map_err(|_| format!("invalid count '{}' in graph '{}'", v, spec));
+        let network = match (kind, fields.as_slice()) {
+            ("regular", &[sites, degree]) => {
+                let (sites, degree) = (count(sites)?, count(degree)?);
+                if degree >= sites || !(sites * degree).is_multiple_of(2) {
+                    return Err(format!("no {}-regular graph on {} sites", degree, sites));
+                }
+                Network::RandomRegular { sites, degree }
+            }
+            ("small-world", &[sites, degree, rewiring]) => {
+                let (sites, degree) = (count(sites)?, count(degree)?);
+                let rewiring = rewiring
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid number '{}' in graph '{}'", rewiring, spec))?;
+                if !degree.is_multiple_of(2) || degree >= sites {
+                    return Err("small-world degree must be even and below the number of sites".to_string());
+                }
+                Network::SmallWorld {
+                    sites,
+                    degree,
+                    rewiring,
+                }
+            }
+            ("scale-free", &[sites, links]) => {
+                let (sites, links) = (count(sites)?, count(links)?);
+                if links == 0 || links >= sites {
+                    return Err("scale-free links must be positive and below the number of sites".to_string());
+                }
+                Network::ScaleFree { sites, links }
+            }
+            _ => return Err(format!("unrecognized graph '{}'", spec)),
+        };
+        Ok(network)
+    }
+
+    pub fn build<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Rc<dyn Lattice>, String> {
+        let lattice: Rc<dyn Lattice> = match *self {
+            Network::Loaded(ref graph) => graph.clone(),
+            Network::RandomRegular { sites, degree } => {
+                // Locally tree-like, so the Bethe lattice transition is exact
+                let critical_temperature = 2.0 / (degree as f64 / (degree as f64 - 2.0)).ln();
+                let mut graph = Graph::from_edges(sites, &random_regular(sites, degree, rng)?);
+                if degree > 2 {
+                    graph.critical_temperature = Some(critical_temperature);
+                }
+                Rc::new(graph)
+            }
+            Network::SmallWorld {
+                sites,
+                degree,
+                rewiring,
+            } => Rc::new(Graph::from_edges(sites, &small_world(sites, degree, rewiring, rng))),
+            Network::ScaleFree { sites, links } => {
+                Rc::new(Graph::from_edges(sites, &scale_free(sites, links, rng)))
+            }
+        };
+        Ok(lattice)
+    }
+}
+
+/// Pairs up `degree` stubs per site at random, rejecting self-loops and multi-edges, and
+/// starts over when the last stubs cannot be paired.
+fn random_regular<R: Rng + ?Sized>(sites: usize, degree: usize, rng: &mut R) -> Result<Vec<(usize, usize)>, String> {
+    let mut edges = Vec::new();
+    for _ in 0..PAIRING_ATTEMPTS {
+        let mut stubs: Vec<usize> = (0..sites).flat_map(|i| std::iter::repeat_n(i, degree)).collect();
+        let mut seen = HashSet::new();
+        edges.clear();
+
+        while stubs.len() >= 2 {
+            let mut paired = false;
+            for _ in 0..stubs.len() {
+                let a = rng.gen_range(0..stubs.len());
+                let b = rng.gen_range(0..stubs.len());
+                let (i, j) = (stubs[a], stubs[b]);
+                if i != j && !seen.contains(&(i.min(j), i.max(j))) {
+                    seen.insert((i.min(j), i.max(j)));
+                    edges.push((i, j));
+                    stubs.swap_remove(a.max(b));
+                    stubs.swap_remove(a.min(b));
+                    paired = true;
+                    break;
+                }
+            }
+            if !paired {
+                break;
+            }
+        }
+        if stubs.is_empty() {
+            return Ok(edges);
+        }
+    }
+    Err(format!(
+        "could not pair a {}-regular graph on {} sites in {} attempts",
+        degree, sites, PAIRING_ATTEMPTS
+    ))
+}
+
+fn small_world<R: Rng + ?Sized>(sites: usize, degree: usize, rewiring: f64, rng: &mut R) -> Vec<(usize, usize)> {
+    let mut seen = HashSet::new();
+    for i in 0..sites {
+        for d in 1..=degree / 2 {
+            let j = (i + d) % sites;
+            seen.insert((i.min(j), i.max(j)));
+        }
+    }
+
+    let mut edges = Vec::new();
+    for i in 0..sites {
+        for d in 1..=degree / 2 {
+            let mut j = (i + d) % sites;
+            if rng.gen::<f64>() < rewiring {
+                let k = rng.gen_range(0..sites);
+                if k != i && !seen.contains(&(i.min(k), i.max(k))) {
+                    seen.remove(&(i.min(j), i.max(j)));
+                    seen.insert((i.min(k), i.max(k)));
+                    j = k;
+                }
+            }
+            edges.push((i, j));
+        }
+    }
+    edges
+}
+
+/// Grows from a clique of `links + 1` sites. Each endpoint of every bond is listed once
+/// in `ends`, so a uniform draw from it picks sites in proportion to their degree.
+fn scale_free<R: Rng + ?Sized>(sites: usize, links: usize, rng: &mut R) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    let mut ends = Vec::new();
+    for i in 0..=links {
+        for j in 0..i {
+            edges.push((i, j));
+            ends.extend([i, j]);
+        }
+    }
+
+    for i in links + 1..sites {
+        let mut targets = HashSet::new();
+        while targets.len() < links {
+            targets.insert(*ends.choose(rng).unwrap());
+        }
+        for j in targets {
+            edges.push((i, j));
+            ends.extend([i, j]);
+        }
+    }
+    edges
+}
+
+/// Maps vertex labels, which may be any strings, to site indices in order of appearance.
+#[derive(Default)]
+struct Labels {
+    indices: HashMap<String, usize>,
+}
+
+impl Labels {
+    fn index(&mut self, label: &str) -> usize {
+        let next = self.indices.len();
+        *self.indices.entry(label.to_string()).or_insert(next)
+    }
+
+    /// Graph on the labelled vertices, dropping self-loops and repeated edges.
+    fn graph(&self, edges: &[(usize, usize)]) -> Graph {
+        let mut seen = HashSet::new();
+        let edges: Vec<(usize, usize)> = edges
+            .iter()
+            .filter(|&&(i, j)| i != j && seen.insert((i.min(j), i.max(j))))
+            .copied()
+            .collect();
+        Graph::from_edges(self.indices.len(), &edges)
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Lines that are neither blank nor `#` or `%` comments, with 1-based line numbers.
+fn content_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .map(|(n, line)| (n + 1, line))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#') && !line.starts_with('%'))
+}
+
+/// Reads `u v` lines, one per edge. Any further columns, such as weights, are ignored.
+fn from_edge_list(path: &str) -> Result<Graph, String> {
+    let contents = read(path)?;
+    let mut labels = Labels::default();
+    let mut edges = Vec::new();
+    for (n, line) in content_lines(&contents) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 2 {
+            return Err(format!("{}:{}: expected 'u v'", path, n));
+        }
+        edges.push((labels.index(fields[0]), labels.index(fields[1])));
+    }
+    if labels.indices.is_empty() {
+        return Err(format!("{}: no edges found", path));
+    }
+    Ok(labels.graph(&edges))
+}
+
+/// Reads `u v1 v2 ...` lines listing the neighbours of each vertex. Edges may be listed
+/// from one or both ends.
+fn from_adjacency_list(path: &str) -> Result<Graph, String> {
+    let contents = read(path)?;
+    let mut labels = Labels::default();
+    let mut edges = Vec::new();
+    for (_, line) in content_lines(&contents) {
+        let mut fields = line.split_whitespace();
+        if let Some(vertex) = fields.next() {
+            let u = labels.index(vertex);
+            for neighbor in fields {
+                edges.push((u, labels.index(neighbor)));
+            }
+        }
+    }
+    if labels.indices.is_empty() {
+        return Err(format!("{}: no vertices found", path));
+    }
+    Ok(labels.graph(&edges))
+}
+
+/// Reads the `node` and `edge` elements of a GraphML file, treating edges as undirected.
+/// Data keys are ignored.
+fn from_graphml(path: &str) -> Result<Graph, String> {
+    let contents = read(path)?;
+    let mut labels = Labels::default();
+    let mut edges = Vec::new();
+    for tag in contents.split('<').filter_map(|piece| piece.split('>').next()) {
+        if let Some(attributes) = tag.strip_prefix("node") {
+            let id = attribute(attributes, "id").ok_or_else(|| format!("{}: node without id", path))?;
+            labels.index(id);
+        } else if let Some(attributes) = tag.strip_prefix("edge") {
+            let source = attribute(attributes, "source");
+            let target = attribute(attributes, "target");
+            match source.zip(target) {
+                Some((source, target)) => edges.push((labels.index(source), labels.index(target))),
+                None => return Err(format!("{}: edge without source and target", path)),
+            }
+        }
+    }
+    if labels.indices.is_empty() {
+        return Err(format!("{}: no nodes found", path));
+    }
+    Ok(labels.graph(&edges))
+}
+
+/// Value of `name="value"` or `name='value'` among the attributes of an XML tag.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    while let Some(start) = rest.find(name) {
+        let preceded = rest[..start].ends_with(char::is_whitespace);
+        let after = rest[start + name.len()..].trim_start();
+        if let Some(value) = after.strip_prefix('=') {
+            let value = value.trim_start();
+            if let Some(quote) = value.chars().next().filter(|&c| c == '"' || c == '\'') {
+                let value = &value[1..];
+                if preceded {
+                    return value.find(quote).map(|end| &value[..end]);
+                }
+            }
+        }
+        rest = &rest[start + name.len()..];
+    }
+    None
+}
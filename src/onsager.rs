@@ -0,0 +1,175 @@
+use std::f64::consts::PI;
+
+use crate::ising::{Ising, Model};
+
+/// Energy per site of the infinite square lattice with equal couplings J in zero field,
+/// Onsager's u = -J coth 2K [1 + (2/π)(2 tanh² 2K - 1) K(κ)] with K = |J| / T and
+/// κ = 2 sinh 2K / cosh² 2K. The elliptic integral diverges at T_c, where its prefactor
+/// vanishes.
+pub fn energy(coupling: f64, temperature: f64) -> f64 {
+    let j = coupling.abs();
+    if temperature <= 0.0 {
+        return -2.0 * j;
+    }
+    let k = 2.0 * j / temperature;
+    let factor = 2.0 * k.tanh().powi(2) - 1.0;
+    let modulus = 2.0 * k.sinh() / k.cosh().powi(2);
+    let (first, _) = elliptic_integrals(modulus);
+    let term = if first.is_finite() { factor * first } else { 0.0 };
+    -j / k.tanh() * (1.0 + 2.0 / PI * term)
+}
+
+/// Specific heat per site of the infinite square lattice with equal couplings in zero
+/// field, the derivative of Onsager's energy through dK/dκ = E(κ) / (κ (1 - κ²)) - K(κ) / κ.
+/// It diverges logarithmically at T_c.
+pub fn specific_heat(coupling: f64, temperature: f64) -> f64 {
+    let j = coupling.abs();
+    if temperature <= 0.0 || j == 0.0 {
+        return 0.0;
+    }
+    let k = j / temperature;
+    let (sinh, cosh) = ((2.0 * k).sinh(), (2.0 * k).cosh());
+    let tanh = sinh / cosh;
+    let modulus = 2.0 * sinh / (cosh * cosh);
+    let (first, second) = elliptic_integrals(modulus);
+    if !first.is_finite() {
+        return f64::INFINITY;
+    }
+    let factor = 2.0 * tanh * tanh - 1.0;
+    let bracket = 1.0 + 2.0 / PI * factor * first;
+
+    // Derivatives with respect to K = |J| / T
+    let d_first = second / (modulus * (1.0 - modulus * modulus)) - first / modulus;
+    let d_modulus = 4.0 * (1.0 - sinh * sinh) / cosh.powi(3);
+    let d_factor = 8.0 * tanh / (cosh * cosh);
+    let d_bracket = 2.0 / PI * (d_factor * first + factor * d_first * d_modulus);
+    let d_energy = -j * (-2.0 / (sinh * sinh) * bracket + d_bracket / tanh);
+    -k * k / j * d_energy
+}
+
+/// Yang's spontaneous magnetization of the infinite square lattice,
+/// [1 - (sinh 2K_x sinh 2K_y)^-2]^(1/8) below T_c and 0 above. With antiferromagnetic
+/// couplings it is the staggered or striped order parameter instead.
+pub fn spontaneous_magnetization(horizontal: f64, vertical: f64, temperature: f64) -> f64 {
+    if temperature <= 0.0 {
+        return if horizontal != 0.0 && vertical != 0.0 { 1.0 } else { 0.0 };
+    }
+    let product = (2.0 * horizontal.abs() / temperature).sinh() * (2.0 * vertical.abs() / temperature).sinh();
+    if product <= 1.0 {
+        0.0
+    } else {
+        (1.0 - product.powi(-2)).powf(0.125)
+    }
+}
+
+/// Complete elliptic integrals K(k) and E(k) of the first and second kinds, from the
+/// arithmetic-geometric mean of 1 and √(1 - k²). K is infinite at k = 1.
+fn elliptic_integrals(k: f64) -> (f64, f64) {
+    if k >= 1.0 {
+        return (f64::INFINITY, 1.0);
+    }
+    let (mut a, mut b) = (1.0, (1.0 - k * k).max(0.0).sqrt());
+    let mut c = k;
+    let mut power = 0.5;
+    let mut sum = power * c * c;
+    for _ in 0..64 {
+        if (a - b).abs() <= 1e-16 * a {
+            break;
+        }
+        c = 0.5 * (a - b);
+        (a, b) = (0.5 * (a + b), (a * b).sqrt());
+        power *= 2.0;
+        sum += power * c * c;
+    }
+    let first = PI / (2.0 * a);
+    (first, first * (1.0 - sum))
+}
+
+/// Square lattice torus of `width` x `height` Ising spins in zero field, solved exactly at
+/// any size by Kaufman's partition function: Z is half the sum of four products of
+/// 2 cosh or 2 sinh of (width/2) γ_l over l of one parity, with
+/// cosh γ_l = cosh 2K_y cosh 2K_x* - sinh 2K_y sinh 2K_x* cos(πl / height), the dual
+/// tanh K_x* = exp(-2 K_x), and γ_0 = 2 (K_y - K_x*) taking its sign.
+#[derive(Debug)]
+pub struct Torus {
+    pub width: usize,
+    pub height: usize,
+    pub horizontal_coupling: f64,
+    pub vertical_coupling: f64,
+}
+
+impl Torus {
+    /// The torus of `ising`, when it is a plain periodic square lattice of Ising spins with
+    /// non-zero couplings, in zero field and without disorder.
+    pub fn of(ising: &Ising) -> Option<Self> {
+        let (width, height) = ising.geometry.square_torus()?;
+        let field_free = ising.magnetic_moment * ising.magnetic_field_strength == 0.0 && ising.fields.iter().all(|&h| h == 0.0);
+        let coupled = ising.horizontal_coupling != 0.0 && ising.vertical_coupling != 0.0;
+        (ising.model == Model::Ising && field_free && ising.is_clean() && coupled).then_some(Self {
+            width,
+            height,
+            horizontal_coupling: ising.horizontal_coupling,
+            vertical_coupling: ising.vertical_coupling,
+        })
+    }
+
+    fn num_sites(&self) -> f64 {
+        (self.width * self.height) as f64
+    }
+
+    /// ln Z at inverse temperature `beta`, with every product summed as logarithms so that
+    /// large tori neither overflow nor lose the signs of the sinh products. Both sides are
+    /// even, so antiferromagnetic couplings give the same Z as ferromagnetic ones.
+    pub fn ln_partition_function(&self, beta: f64) -> f64 {
+        let kx = beta * self.horizontal_coupling.abs();
+        let ky = beta * self.vertical_coupling.abs();
+        let dual = -kx.tanh().ln() / 2.0;
+        let (cosh, sinh) = ((2.0 * ky).cosh() * (2.0 * dual).cosh(), (2.0 * ky).sinh() * (2.0 * dual).sinh());
+        let gamma = |l: usize| match l {
+            0 => 2.0 * (ky - dual),
+            _ => (cosh - sinh * (PI * l as f64 / self.height as f64).cos()).acosh(),
+        };
+        let half = self.width as f64 / 2.0;
+
+        // (sign, ln |product|) of the four products
+        let mut products = [(1.0, 0.0); 4];
+        for r in 0..self.height {
+            for (parity, l) in [(0, 2 * r + 1), (1, 2 * r)] {
+                let x = half * gamma(l);
+                let tail = (-2.0 * x.abs()).exp();
+                let (sign, ln_sinh) = (x.signum(), x.abs() + (-tail).ln_1p());
+                let cosh_product = &mut products[2 * parity];
+                cosh_product.1 += x.abs() + tail.ln_1p();
+                let sinh_product = &mut products[2 * parity + 1];
+                sinh_product.0 *= sign;
+                sinh_product.1 += ln_sinh;
+            }
+        }
+        let largest = products.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        let sum: f64 = products.iter().map(|&(sign, ln)| sign * (ln - largest).exp()).sum();
+        -(2f64).ln() + self.num_sites() / 2.0 * (2.0 * (2.0 * kx).sinh()).ln() + largest + sum.ln()
+    }
+
+    /// Energy per site, -∂ ln Z / ∂β / N by central differences.
+    pub fn energy(&self, temperature: f64) -> f64 {
+        if temperature <= 0.0 {
+            return -self.horizontal_coupling.abs() - self.vertical_coupling.abs();
+        }
+        let (beta, step) = (1.0 / temperature, 1e-4);
+        let up = self.ln_partition_function(beta * (1.0 + step));
+        let down = self.ln_partition_function(beta * (1.0 - step));
+        -(up - down) / (2.0 * step * beta) / self.num_sites()
+    }
+
+    /// Specific heat per site, β² ∂² ln Z / ∂β² / N by central differences.
+    pub fn specific_heat(&self, temperature: f64) -> f64 {
+        if temperature <= 0.0 {
+            return 0.0;
+        }
+        let (beta, step) = (1.0 / temperature, 1e-4);
+        let up = self.ln_partition_function(beta * (1.0 + step));
+        let middle = self.ln_partition_function(beta);
+        let down = self.ln_partition_function(beta * (1.0 - step));
+        (up - 2.0 * middle + down) / (step * step) / self.num_sites()
+    }
+}
@@ -1,9 +1,15 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ParameterType {
     Temp,
-    Coupling,
+    HorizontalCoupling,
+    VerticalCoupling,
+    DiagonalCoupling,
+    AxialCoupling,
+    CrystalField,
+    Anisotropy,
     MagMoment,
     MagFieldStrength,
+    Disorder,
 }
 
 #[derive(Debug)]
@@ -11,6 +17,7 @@ pub struct Parameter {
     value: u16,
     step: u16,
     bounds: (u16, u16),
+    scaled_min: f64,
     scaled_max: f64,
 }
 
@@ -20,10 +27,19 @@ impl Parameter {
             value,
             step,
             bounds,
+            scaled_min: 0.0,
             scaled_max,
         }
     }
 
+    /// A parameter spanning [-scaled_max, scaled_max] instead of [0, scaled_max].
+    pub fn new_signed(value: u16, step: u16, bounds: (u16, u16), scaled_max: f64) -> Self {
+        Self {
+            scaled_min: -scaled_max,
+            ..Self::new(value, step, bounds, scaled_max)
+        }
+    }
+
     pub fn increase_value(&mut self, value: u16) {
         if self.value <= self.bounds.1 - value {
             self.value += value;
@@ -41,7 +57,14 @@ impl Parameter {
     }
 
     pub fn scaled(&self) -> f64 {
-        self.normalized() * self.scaled_max
+        self.scaled_min + self.normalized() * (self.scaled_max - self.scaled_min)
+    }
+
+    /// Moves the gauge as close as its resolution allows to a scaled value.
+    pub fn set_scaled(&mut self, scaled: f64) {
+        let normalized = (scaled - self.scaled_min) / (self.scaled_max - self.scaled_min);
+        let value = (normalized * self.bounds.1 as f64).round();
+        self.value = value.clamp(self.bounds.0 as f64, self.bounds.1 as f64) as u16;
     }
 
     pub fn step(&self) -> &u16 {
@@ -0,0 +1,262 @@
+use rand::Rng;
+use std::f64::consts::PI;
+
+use crate::ising::{Bond, Ising};
+
+/// Numerical Berezinskii-Kosterlitz-Thouless temperature of the square lattice XY model
+/// with unit coupling.
+const XY_BKT_TEMPERATURE: f64 = 0.8929;
+
+/// Planar rotors θ_i on the sites of an `Ising` system, which provides the bonds,
+/// couplings, temperature and fields: the XY model, or the p-state clock model with
+/// θ_i restricted to multiples of 2π/p. The energy is -Σ J_ij cos(θ_i - θ_j) - Σ h_i cos θ_i,
+/// with every field along θ = 0 and fixed boundary spins at θ = 0 or π.
+#[derive(Debug)]
+pub struct PlanarSpins {
+    /// Number of clock states p, or None for continuous angles.
+    pub clock: Option<usize>,
+    pub angles: Vec<f64>,
+    /// Overrelaxation steps after every Metropolis step.
+    pub overrelaxation: bool,
+    /// Half width of the Metropolis angle proposals, tuned between runs towards an
+    /// acceptance rate of one half.
+    window: f64,
+    /// Corners of the square plaquettes whose four sides are bonds, in cyclic order.
+    plaquettes: Vec<[usize; 4]>,
+    /// Bonds from each site to its neighbour along +x, for the helicity modulus.
+    forward_bonds: Vec<(usize, Bond)>,
+    /// Samples of Σ J cos(θ_i - θ_j) and (Σ J sin(θ_i - θ_j))² over the forward bonds,
+    /// collected at `sampled_temperature`.
+    samples: (usize, f64, f64),
+    sampled_temperature: f64,
+}
+
+impl PlanarSpins {
+    /// Rotors on the sites of `ising`, drawn independently. Its drawing defines the
+    /// plaquettes and the x axis.
+    pub fn new<R: Rng + ?Sized>(ising: &Ising, clock: Option<usize>, rng: &mut R) -> Self {
+        let canvas = ising.canvas(0);
+        let (width, height) = (canvas.width, canvas.height);
+        let site_at = |x: usize, y: usize| canvas.sites[(y % height) * width + x % width];
+        let bond = |i: usize, j: usize| ising.bonds[i].iter().find(|bond| bond.site == j).copied();
+
+        let mut plaquettes = Vec::new();
+        let mut forward_bonds = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let corners = [site_at(x, y), site_at(x + 1, y), site_at(x + 1, y + 1), site_at(x, y + 1)];
+                if let [Some(a), Some(b), Some(c), Some(d)] = corners {
+                    if let Some(forward) = bond(a, b) {
+                        forward_bonds.push((a, forward));
+                    }
+                    if [(a, b), (b, c), (c, d), (d, a)].iter().all(|&(i, j)| bond(i, j).is_some()) {
+                        plaquettes.push([a, b, c, d]);
+                    }
+                }
+            }
+        }
+
+        let mut spins = Self {
+            clock,
+            angles: vec![0.0; ising.lattice.len()],
+            overrelaxation: clock.is_none(),
+            window: PI,
+            plaquettes,
+            forward_bonds,
+            samples: (0, 0.0, 0.0),
+            sampled_temperature: f64::NAN,
+        };
+        spins.randomize(rng);
+        spins
+    }
+
+    pub fn randomize<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for k in 0..self.angles.len() {
+            self.angles[k] = match self.clock {
+                Some(states) => self.clock_angle(rng.gen_range(0..states)),
+                None => rng.gen_range(0.0..2.0 * PI),
+            };
+        }
+    }
+
+    fn clock_angle(&self, state: usize) -> f64 {
+        2.0 * PI * state as f64 / self.clock.unwrap_or(1) as f64
+    }
+
+    /// Field (h_x, h_y) acting on a site, from its neighbours and external fields.
+    fn local_field(&self, ising: &Ising, idx: usize) -> (f64, f64) {
+        let (mut hx, mut hy) = (0.0, 0.0);
+        for bond in ising.bonds[idx].iter().filter(|bond| ising.occupancy[bond.site]) {
+            let coupling = ising.coupling(bond.kind) * bond.weight;
+            let angle = self.angles[bond.site];
+            hx += coupling * angle.cos();
+            hy += coupling * angle.sin();
+        }
+        (hx + external_field(ising, idx), hy)
+    }
+
+    /// Metropolis update of a random site, proposing an angle within the window around
+    /// the current one, or another clock state drawn uniformly. Returns whether it was
+    /// accepted.
+    pub fn metropolis_step<R: Rng + ?Sized>(&mut self, ising: &Ising, rng: &mut R) -> bool {
+        let idx = rng.gen_range(0..self.angles.len());
+        if !ising.occupancy[idx] {
+            return false;
+        }
+        let old = self.angles[idx];
+        let new = match self.clock {
+            Some(states) => {
+                let current = (old / (2.0 * PI) * states as f64).round() as usize % states;
+                let state = (current + rng.gen_range(1..states)) % states;
+                self.clock_angle(state)
+            }
+            None => old + rng.gen_range(-self.window..self.window),
+        };
+        let (hx, hy) = self.local_field(ising, idx);
+        let energy_diff = -(hx * (new.cos() - old.cos()) + hy * (new.sin() - old.sin()));
+        if energy_diff <= 0.0 || rng.gen::<f64>() < (-energy_diff / ising.temperature).exp() {
+            self.angles[idx] = new.rem_euclid(2.0 * PI);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Reflects the rotor of a random site about its local field, which leaves the energy
+    /// unchanged and moves the configuration much further than a Metropolis step. Only
+    /// for continuous angles.
+    pub fn overrelax<R: Rng + ?Sized>(&mut self, ising: &Ising, rng: &mut R) {
+        let idx = rng.gen_range(0..self.angles.len());
+        if !ising.occupancy[idx] {
+            return;
+        }
+        let (hx, hy) = self.local_field(ising, idx);
+        if hx != 0.0 || hy != 0.0 {
+            let direction = hy.atan2(hx);
+            self.angles[idx] = (2.0 * direction - self.angles[idx]).rem_euclid(2.0 * PI);
+        }
+    }
+
+    /// `steps` Metropolis steps, each followed by an overrelaxation step when enabled.
+    pub fn run<R: Rng + ?Sized>(&mut self, ising: &Ising, steps: usize, rng: &mut R) {
+        let mut accepted = 0;
+        for _ in 0..steps {
+            if self.metropolis_step(ising, rng) {
+                accepted += 1;
+            }
+            if self.overrelaxation && self.clock.is_none() {
+                self.overrelax(ising, rng);
+            }
+        }
+        if steps > 0 {
+            let factor = if 2 * accepted > steps { 1.1 } else { 0.9 };
+            self.window = (self.window * factor).clamp(0.01, PI);
+        }
+    }
+
+    /// Length of the mean rotor per occupied site.
+    pub fn magnetization(&self, ising: &Ising) -> f64 {
+        let (mx, my) = self.occupied(ising).fold((0.0, 0.0), |(mx, my), angle| (mx + angle.cos(), my + angle.sin()));
+        (mx * mx + my * my).sqrt() / ising.num_occupied() as f64
+    }
+
+    /// Total energy per occupied site.
+    pub fn energy(&self, ising: &Ising) -> f64 {
+        let total: f64 = (0..self.angles.len())
+            .filter(|&idx| ising.occupancy[idx])
+            .map(|idx| {
+                let (hx, hy) = self.local_field(ising, idx);
+                let external = external_field(ising, idx);
+                let (cos, sin) = (self.angles[idx].cos(), self.angles[idx].sin());
+                // Bonds are seen from both ends, external fields once
+                -0.5 * ((hx - external) * cos + hy * sin) - external * cos
+            })
+            .sum();
+        total / ising.num_occupied() as f64
+    }
+
+    fn occupied<'a>(&'a self, ising: &'a Ising) -> impl Iterator<Item = f64> + 'a {
+        self.angles
+            .iter()
+            .zip(ising.occupancy.iter())
+            .filter(|(_, &occupied)| occupied)
+            .map(|(&angle, _)| angle)
+    }
+
+    /// Vortices and antivortices per site: plaquettes around which the angle winds by ±2π.
+    pub fn vortex_density(&self, ising: &Ising) -> f64 {
+        let wrap = |difference: f64| (difference + PI).rem_euclid(2.0 * PI) - PI;
+        let vortices = self
+            .plaquettes
+            .iter()
+            .filter(|corners| corners.iter().all(|&idx| ising.occupancy[idx]))
+            .filter(|corners| {
+                let winding: f64 = (0..4)
+                    .map(|k| wrap(self.angles[corners[(k + 1) % 4]] - self.angles[corners[k]]))
+                    .sum();
+                winding.abs() > PI
+            })
+            .count();
+        vortices as f64 / ising.num_occupied() as f64
+    }
+
+    /// Adds the current configuration to the helicity modulus estimate, starting over
+    /// whenever the temperature has changed.
+    pub fn measure(&mut self, ising: &Ising) {
+        if ising.temperature != self.sampled_temperature {
+            self.samples = (0, 0.0, 0.0);
+            self.sampled_temperature = ising.temperature;
+        }
+        let (mut cosines, mut sines) = (0.0, 0.0);
+        for (i, bond) in self.forward_bonds.iter() {
+            if ising.occupancy[*i] && ising.occupancy[bond.site] {
+                let coupling = ising.coupling(bond.kind) * bond.weight;
+                let difference = self.angles[*i] - self.angles[bond.site];
+                cosines += coupling * difference.cos();
+                sines += coupling * difference.sin();
+            }
+        }
+        self.samples.0 += 1;
+        self.samples.1 += cosines;
+        self.samples.2 += sines * sines;
+    }
+
+    /// Helicity modulus Υ = (⟨Σ J cos(θ_i - θ_j)⟩ - ⟨(Σ J sin(θ_i - θ_j))²⟩ / T) / N over the
+    /// bonds along x, the stiffness against a twist of the boundary. It jumps from
+    /// 2 T_BKT / π to 0 at the BKT transition.
+    pub fn helicity_modulus(&self, ising: &Ising) -> f64 {
+        let (count, cosines, sines) = self.samples;
+        if count == 0 {
+            return 0.0;
+        }
+        let count = count as f64;
+        (cosines / count - sines / count / ising.temperature) / ising.num_occupied() as f64
+    }
+
+    /// Transition temperature on the plain square lattice with equal couplings, and
+    /// whether it is exact: the XY model's BKT temperature from Monte Carlo, and the exact
+    /// Ising, 3-state Potts and decoupled Ising transitions of the 2, 3 and 4-state clock
+    /// models. Clock models with p ≥ 5 have two BKT transitions instead.
+    pub fn critical_temperature(&self, ising: &Ising) -> Option<(f64, bool)> {
+        let coupling = ising.horizontal_coupling;
+        let geometry = &ising.geometry;
+        if coupling <= 0.0 || ising.vertical_coupling != coupling {
+            return None;
+        }
+        // Of the lattices, only the plain square one knows its Potts transitions
+        geometry.potts_critical_temperature(1.0, 1.0, 2)?;
+        match self.clock {
+            None => Some((XY_BKT_TEMPERATURE * coupling, false)),
+            Some(2) => geometry.critical_temperature(coupling, coupling).map(|t| (t, true)),
+            Some(3) => geometry.potts_critical_temperature(1.5 * coupling, 1.5 * coupling, 3).map(|t| (t, true)),
+            Some(4) => geometry.critical_temperature(0.5 * coupling, 0.5 * coupling).map(|t| (t, true)),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Uniform, local and boundary wall field along θ = 0 acting on a site.
+fn external_field(ising: &Ising, idx: usize) -> f64 {
+    let walls: f64 = ising.walls[idx].iter().map(|wall| ising.coupling(wall.kind) * wall.spin).sum();
+    ising.magnetic_moment * ising.magnetic_field_strength + ising.fields[idx] + walls
+}
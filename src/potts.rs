@@ -0,0 +1,80 @@
+use rand::Rng;
+use std::rc::Rc;
+
+use crate::cluster::Algorithm;
+use crate::ising::{Ising, Model};
+use crate::lattice::{Lattice, Rectangular};
+
+/// Side by side heating runs of the Ising model and of q-state Potts models on the L x L
+/// square lattice with unit couplings, at the same temperatures relative to their exact
+/// T_c. The order parameter and energy fall continuously through T_c for the Ising model
+/// and q ≤ 4, but jump at the first order transition of q ≥ 5, by more the larger q.
+#[derive(Debug)]
+pub struct Comparison {
+    /// Numbers of Potts states run besides the Ising model.
+    pub states: Vec<i8>,
+    /// Measurement sweeps at every temperature, after a quarter as many to equilibrate.
+    pub sweeps: usize,
+    pub algorithm: Algorithm,
+}
+
+impl Default for Comparison {
+    fn default() -> Self {
+        Self {
+            states: vec![3, 4, 5, 8],
+            sweeps: 1000,
+            algorithm: Algorithm::SwendsenWang,
+        }
+    }
+}
+
+impl Comparison {
+    /// Models compared, the Ising model first.
+    pub fn models(&self) -> Vec<Model> {
+        let potts = self.states.iter().map(|&states| Model::Potts { states });
+        std::iter::once(Model::Ising).chain(potts).collect()
+    }
+
+    /// Mean order parameter and energy per site of every model at each of the reduced
+    /// temperatures T / T_c, in the order given, starting from the ordered state and
+    /// carrying the configuration from one temperature to the next.
+    pub fn run<R: Rng + ?Sized>(&self, size: usize, reduced_temperatures: &[f64], rng: &mut R) -> Vec<Vec<(f64, f64)>> {
+        let geometry: Rc<dyn Lattice> = Rc::new(Rectangular::square(size));
+        self.models()
+            .into_iter()
+            .map(|model| {
+                let critical_temperature = match model {
+                    Model::Ising => geometry.critical_temperature(1.0, 1.0),
+                    Model::Potts { states } => geometry.potts_critical_temperature(1.0, 1.0, states as usize),
+                    Model::BlumeCapel => None,
+                }
+                .expect("compared models are solved on the square lattice");
+                let mut ising = Ising::new(geometry.clone(), rng);
+                ising.model = model;
+                ising.lattice.iter_mut().for_each(|s| *s = 1);
+
+                reduced_temperatures
+                    .iter()
+                    .map(|&t| {
+                        ising.temperature = t * critical_temperature;
+                        let sweep = |ising: &mut Ising, rng: &mut R| {
+                            let num_sites = ising.lattice.len();
+                            self.algorithm.run(ising, num_sites, rng);
+                        };
+                        for _ in 0..self.sweeps / 4 {
+                            sweep(&mut ising, rng);
+                        }
+                        let (mut magnetization, mut energy) = (0.0, 0.0);
+                        for _ in 0..self.sweeps {
+                            sweep(&mut ising, rng);
+                            magnetization += ising.magnetization().abs();
+                            energy += ising.energy();
+                        }
+                        let samples = self.sweeps.max(1) as f64;
+                        (magnetization / samples, energy / samples)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+
+use crate::anneal::Solution;
+
+/// Input file formats accepted by the solver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// `i j value` lines, 0-based; i = j gives a field.
+    Ising,
+    /// `i j Q_ij` lines, 0-based, minimizing x^T Q x over x ∈ {0, 1}.
+    Qubo,
+    /// `n m` header then `i j w` lines, 1-based, maximizing the cut.
+    Gset,
+    /// `p edge n m` header then `e i j [w]` lines, 1-based, maximizing the cut.
+    Dimacs,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "ising" => Ok(Format::Ising),
+            "qubo" => Ok(Format::Qubo),
+            "gset" => Ok(Format::Gset),
+            "dimacs" => Ok(Format::Dimacs),
+            _ => Err(format!("unrecognized format '{}'", name)),
+        }
+    }
+}
+
+/// What the Ising energy of a problem stands for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+    Energy,
+    /// x^T Q x = E + offset
+    Qubo { offset: f64 },
+    /// cut = offset - E
+    Cut { offset: f64 },
+}
+
+/// An Ising optimization problem, E = -Σ J_ij s_i s_j - Σ h_i s_i, over `num_spins` spins.
+#[derive(Debug)]
+pub struct Problem {
+    pub num_spins: usize,
+    pub couplings: Vec<(usize, usize, f64)>,
+    pub fields: Vec<f64>,
+    pub objective: Objective,
+}
+
+impl Problem {
+    pub fn from_file(path: &str, format: Format) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let problem = match format {
+            Format::Ising => Self::from_ising(path, &contents),
+            Format::Qubo => Self::from_qubo(path, &contents),
+            Format::Gset => Self::from_gset(path, &contents),
+            Format::Dimacs => Self::from_dimacs(path, &contents),
+        }?;
+
+        if problem.num_spins == 0 {
+            return Err(format!("{}: problem has no entries", path));
+        }
+        Ok(problem)
+    }
+
+    /// One `i j value` entry per line with 0-based indices. Entries with i = j are local
+    /// fields h_i, all others couplings J_ij. Lines starting with `#` are ignored.
+    fn from_ising(path: &str, contents: &str) -> Result<Self, String> {
+        let entries = triples(path, numbered_lines(contents), 0)?;
+
+        let num_spins = entries.iter().map(|&(i, j, _)| i.max(j) + 1).max().unwrap_or(0);
+        let mut problem = Self::empty(num_spins, Objective::Energy);
+        for (i, j, value) in entries {
+            if i == j {
+                problem.fields[i] += value;
+            } else {
+                problem.couplings.push((i, j, value));
+            }
+        }
+
+        Ok(problem)
+    }
+
+    /// QUBO matrix entries `i j Q_ij` with 0-based indices, in the qbsolv layout: `c`
+    /// comment lines and a `p qubo ...` header are skipped. Substituting x = (1 + s) / 2
+    /// gives J_ij = -Q_ij / 4, h_i = -Q_ii / 2 - Σ_j Q_ij / 4 and a constant offset.
+    fn from_qubo(path: &str, contents: &str) -> Result<Self, String> {
+        let lines = numbered_lines(contents).filter(|(_, line)| !line.starts_with('c') && !line.starts_with('p'));
+        let entries = triples(path, lines, 0)?;
+
+        let num_spins = entries.iter().map(|&(i, j, _)| i.max(j) + 1).max().unwrap_or(0);
+        let mut problem = Self::empty(num_spins, Objective::Qubo { offset: 0.0 });
+        let mut offset = 0.0;
+        for (i, j, q) in entries {
+            if i == j {
+                problem.fields[i] -= q / 2.0;
+                offset += q / 2.0;
+            } else {
+                problem.couplings.push((i, j, -q / 4.0));
+                problem.fields[i] -= q / 4.0;
+                problem.fields[j] -= q / 4.0;
+                offset += q / 4.0;
+            }
+        }
+        problem.objective = Objective::Qubo { offset };
+
+        Ok(problem)
+    }
+
+    /// Gset graphs: an `n m` header followed by 1-based `i j w` edges.
+    fn from_gset(path: &str, contents: &str) -> Result<Self, String> {
+        let mut lines = numbered_lines(contents);
+        let (n, header) = lines.next().unwrap_or((0, ""));
+        let num_spins = match header.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [vertices, _] => vertices
+                .parse()
+                .map_err(|_| format!("{}:{}: invalid vertex count", path, n + 1))?,
+            _ => return Err(format!("{}:{}: expected 'vertices edges'", path, n + 1)),
+        };
+
+        let edges = triples(path, lines, 1)?;
+        Self::from_edges(path, num_spins, edges)
+    }
+
+    /// DIMACS-like edge lists: `c` comments, a `p edge n m` header and `e i j [w]` edges
+    /// with 1-based vertices and unit default weight.
+    fn from_dimacs(path: &str, contents: &str) -> Result<Self, String> {
+        let mut num_spins = None;
+        let mut edges = Vec::new();
+        for (n, line) in contents.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let error = || format!("{}:{}: malformed line", path, n + 1);
+            match fields.as_slice() {
+                [] | ["c", ..] => {}
+                ["p", _, vertices, _] => num_spins = Some(vertices.parse().map_err(|_| error())?),
+                ["e", i, j, rest @ ..] => {
+                    let weight = match rest {
+                        [] => 1.0,
+                        [w] => w.parse().map_err(|_| error())?,
+                        _ => return Err(error()),
+                    };
+                    let (i, j) = (vertex(i, 1).ok_or_else(error)?, vertex(j, 1).ok_or_else(error)?);
+                    edges.push((i, j, weight));
+                }
+                _ => return Err(error()),
+            }
+        }
+
+        let num_spins = num_spins.ok_or_else(|| format!("{}: missing 'p edge' header", path))?;
+        Self::from_edges(path, num_spins, edges)
+    }
+
+    /// Max-cut on a weighted graph. cut = Σ w_ij (1 - s_i s_j) / 2, so J_ij = -w_ij / 2
+    /// and cut = W / 2 - E with W the total weight.
+    fn from_edges(path: &str, num_spins: usize, edges: Vec<(usize, usize, f64)>) -> Result<Self, String> {
+        let mut problem = Self::empty(num_spins, Objective::Cut { offset: 0.0 });
+        let mut total_weight = 0.0;
+        for (i, j, w) in edges {
+            if i >= num_spins || j >= num_spins {
+                return Err(format!("{}: edge {}-{} out of range", path, i + 1, j + 1));
+            }
+            if i != j {
+                problem.couplings.push((i, j, -w / 2.0));
+                total_weight += w;
+            }
+        }
+        problem.objective = Objective::Cut { offset: total_weight / 2.0 };
+
+        Ok(problem)
+    }
+
+    fn empty(num_spins: usize, objective: Objective) -> Self {
+        Self {
+            num_spins,
+            couplings: Vec::new(),
+            fields: vec![0.0; num_spins],
+            objective,
+        }
+    }
+
+    /// Maps a solution back to the problem's own variables and objective value.
+    pub fn interpret(&self, solution: &Solution) -> String {
+        let bits = || {
+            solution
+                .spins
+                .iter()
+                .map(|&s| if s > 0 { "1" } else { "0" })
+                .collect::<Vec<&str>>()
+                .join(" ")
+        };
+        match self.objective {
+            Objective::Energy => solution.to_string(),
+            Objective::Qubo { offset } => format!("objective: {}\nx: {}", solution.energy + offset, bits()),
+            Objective::Cut { offset } => format!("cut: {}\npartition: {}", offset - solution.energy, bits()),
+        }
+    }
+}
+
+/// Trimmed lines with their 0-based line number, without blank lines and `#` comments.
+fn numbered_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Parses `i j value` lines, shifting indices down by `base`.
+fn triples<'a>(
+    path: &str,
+    lines: impl Iterator<Item = (usize, &'a str)>,
+    base: usize,
+) -> Result<Vec<(usize, usize, f64)>, String> {
+    let mut entries = Vec::new();
+    for (n, line) in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let entry = match fields.as_slice() {
+            [i, j, value] => match (vertex(i, base), vertex(j, base), value.parse::<f64>()) {
+                (Some(i), Some(j), Ok(value)) => Some((i, j, value)),
+                _ => None,
+            },
+            _ => None,
+        };
+        match entry {
+            Some(entry) => entries.push(entry),
+            None => return Err(format!("{}:{}: expected 'i j value'", path, n + 1)),
+        }
+    }
+    Ok(entries)
+}
+
+fn vertex(field: &str, base: usize) -> Option<usize> {
+    field.parse::<usize>().ok()?.checked_sub(base)
+}
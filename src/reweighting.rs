@@ -0,0 +1,437 @@
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::cluster::Algorithm;
+use crate::exact::{self, Thermodynamics};
+use crate::ising::Ising;
+use crate::wang_landau::LEVEL_RESOLUTION;
+
+/// Samples kept per series in the interactive view.
+const SERIES_LENGTH: usize = 4096;
+/// Series kept in the interactive view, the oldest dropped first.
+const MAX_SERIES: usize = 8;
+/// Samples skipped after the temperature or field changes, while the system settles.
+const SETTLING: usize = 50;
+/// Largest change of any ln Z at which the multi-histogram equations count as solved.
+const TOLERANCE: f64 = 1e-9;
+const MAX_ITERATIONS: usize = 10000;
+
+/// Time series of the total interaction energy E (bonds, local and wall fields, crystal
+/// field) and total magnetization M, sampled at a temperature in a uniform field h = μB.
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub temperature: f64,
+    pub field: f64,
+    pub samples: Vec<(f64, f64)>,
+}
+
+/// (E, M) of `ising`, E without the uniform field.
+pub fn sample(ising: &Ising) -> (f64, f64) {
+    let magnetization: f64 = ising.lattice.iter().map(|&s| s as f64).sum();
+    let field = ising.magnetic_moment * ising.magnetic_field_strength;
+    (ising.total_energy() + field * magnetization, magnetization)
+}
+
+/// Writes a '# sites N' line, then 'T h E M' lines, one per sample.
+pub fn write(path: &str, sites: usize, series: &[Series]) -> Result<(), String> {
+    let mut lines = format!("# sites {}\n", sites);
+    for series in series {
+        for (energy, magnetization) in series.samples.iter() {
+            lines.push_str(&format!("{} {} {} {}\n", series.temperature, series.field, energy, magnetization));
+        }
+    }
+    fs::write(path, lines).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Reads what `write` writes, gathering the samples of every (T, h).
+pub fn load(path: &str) -> Result<(usize, Vec<Series>), String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut sites = None;
+    let mut series: Vec<Series> = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(value) = comment.trim().strip_prefix("sites") {
+                let value = value.trim().parse().map_err(|_| format!("{}:{}: invalid number of sites '{}'", path, number + 1, line))?;
+                sites = Some(value);
+            }
+            continue;
+        }
+        let fields: Vec<f64> = line
+            .split_whitespace()
+            .map(|field| field.parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("{}:{}: expected 'T h E M', got '{}'", path, number + 1, line))?;
+        match fields.as_slice() {
+            [] => {}
+            &[temperature, field, energy, magnetization] if temperature > 0.0 => {
+                match series.iter_mut().find(|s| s.temperature == temperature && s.field == field) {
+                    Some(series) => series.samples.push((energy, magnetization)),
+                    None => series.push(Series { temperature, field, samples: vec![(energy, magnetization)] }),
+                }
+            }
+            _ => return Err(format!("{}:{}: expected 'T h E M' with T > 0, got '{}'", path, number + 1, line)),
+        }
+    }
+    match sites {
+        Some(sites) if sites > 0 => Ok((sites, series)),
+        _ => Err(format!("{}: missing '# sites N' line", path)),
+    }
+}
+
+#[derive(Debug)]
+struct Run {
+    beta: f64,
+    field: f64,
+    samples: f64,
+    /// Standard deviation of E - hM over the run.
+    spread: f64,
+    ln_z: f64,
+}
+
+/// Ferrenberg-Swendsen reweighting of one or several series to other temperatures and
+/// fields. The partition functions Z_k of the runs solve
+/// Z_k = Σ_s e^{-β_k H_k(s)} / Σ_j n_j e^{-β_j H_j(s)} / Z_j over all samples s, with
+/// H_k = E - h_k M, which for a single run reduces to weighting its samples by
+/// e^{-(β - β_k) H}. Samples of equal (E, M) are counted together.
+#[derive(Debug)]
+pub struct Reweighting {
+    sites: f64,
+    runs: Vec<Run>,
+    /// Distinct (E, M) sampled and how often, over all series.
+    states: Vec<(f64, f64, f64)>,
+    /// ln Σ_j n_j e^{-β_j H_j} / Z_j of every state.
+    ln_denominators: Vec<f64>,
+}
+
+impl Reweighting {
+    /// Solves the equations for `series` to within `TOLERANCE`.
+    pub fn new(sites: usize, series: &[Series]) -> Result<Self, String> {
+        let mut reweighting = Self::start(sites, series, None)?;
+        reweighting.iterate(MAX_ITERATIONS, TOLERANCE);
+        Ok(reweighting)
+    }
+
+    /// Sets up the equations for `series` without solving them, each ln Z starting from
+    /// the estimate of `previous` at its (T, h) if given, so that a few iterations
+    /// suffice after samples are added.
+    pub fn start(sites: usize, series: &[Series], previous: Option<&Reweighting>) -> Result<Self, String> {
+        let series: Vec<&Series> = series.iter().filter(|s| !s.samples.is_empty()).collect();
+        if series.is_empty() {
+            return Err("no samples to reweight".to_string());
+        }
+        let mut counts: BTreeMap<(i64, i64), (f64, f64, f64)> = BTreeMap::new();
+        let mut runs: Vec<Run> = series
+            .iter()
+            .map(|series| {
+                let n = series.samples.len() as f64;
+                let (mut sum, mut sum_squares) = (0.0, 0.0);
+                for &(energy, magnetization) in series.samples.iter() {
+                    let key = ((energy / LEVEL_RESOLUTION).round() as i64, magnetization.round() as i64);
+                    counts.entry(key).or_insert((energy, magnetization, 0.0)).2 += 1.0;
+                    let hamiltonian = energy - series.field * magnetization;
+                    sum += hamiltonian;
+                    sum_squares += hamiltonian * hamiltonian;
+                }
+                let mean = sum / n;
+                Run {
+                    beta: 1.0 / series.temperature,
+                    field: series.field,
+                    samples: n,
+                    spread: (sum_squares / n - mean * mean).max(0.0).sqrt(),
+                    ln_z: 0.0,
+                }
+            })
+            .collect();
+        if let Some(previous) = previous {
+            let ln_z: Vec<f64> = runs.iter().map(|run| previous.ln_partition_function(run.beta, run.field)).collect();
+            for (run, value) in runs.iter_mut().zip(ln_z.iter()) {
+                run.ln_z = value - ln_z[0];
+            }
+        }
+        let states: Vec<(f64, f64, f64)> = counts.into_values().collect();
+        let mut reweighting = Self {
+            sites: sites as f64,
+            runs,
+            ln_denominators: vec![0.0; states.len()],
+            states,
+        };
+        reweighting.update_denominators();
+        Ok(reweighting)
+    }
+
+    /// Iterates the equations for ln Z_k at most `iterations` times, with ln Z of the first
+    /// run held at 0, and returns whether the largest change fell below `tolerance`.
+    pub fn iterate(&mut self, iterations: usize, tolerance: f64) -> bool {
+        for _ in 0..iterations {
+            let ln_z: Vec<f64> = self.runs.iter().map(|run| self.ln_partition_function(run.beta, run.field)).collect();
+            let mut change: f64 = 0.0;
+            for (run, value) in self.runs.iter_mut().zip(ln_z.iter()) {
+                change = change.max((value - ln_z[0] - run.ln_z).abs());
+                run.ln_z = value - ln_z[0];
+            }
+            self.update_denominators();
+            if change < tolerance {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn update_denominators(&mut self) {
+        for (&(energy, magnetization, _), ln_denominator) in self.states.iter().zip(self.ln_denominators.iter_mut()) {
+            *ln_denominator = log_sum_exp(
+                self.runs
+                    .iter()
+                    .map(|run| run.samples.ln() - run.beta * (energy - run.field * magnetization) - run.ln_z),
+            );
+        }
+    }
+
+    /// ln of every state's weight at (β, h).
+    fn ln_weights(&self, beta: f64, field: f64) -> impl Iterator<Item = f64> + Clone + '_ {
+        self.states
+            .iter()
+            .zip(self.ln_denominators.iter())
+            .map(move |(&(energy, magnetization, count), ln_denominator)| {
+                count.ln() - beta * (energy - field * magnetization) - ln_denominator
+            })
+    }
+
+    fn ln_partition_function(&self, beta: f64, field: f64) -> f64 {
+        log_sum_exp(self.ln_weights(beta, field))
+    }
+
+    /// Number of series and of samples over all of them.
+    pub fn samples(&self) -> (usize, usize) {
+        (self.runs.len(), self.runs.iter().map(|run| run.samples as usize).sum())
+    }
+
+    /// Temperatures the series reach, within 1/σ in β of every run, σ the standard
+    /// deviation of its energy, beyond which few samples carry the estimates.
+    pub fn range(&self) -> (f64, f64) {
+        let (lowest, highest) = self.runs.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), run| {
+            let reach = 1.0 / run.spread.max(1.0);
+            (lo.min(run.beta - reach), hi.max(run.beta + reach))
+        });
+        let smallest = self.runs.iter().map(|run| run.beta).fold(f64::INFINITY, f64::min);
+        (1.0 / highest, 1.0 / lowest.max(smallest / 2.0))
+    }
+
+    /// Thermodynamics at `temperature` in a uniform field `field`, with no free energy,
+    /// which the series fix only up to a constant.
+    pub fn thermodynamics(&self, temperature: f64, field: f64) -> Thermodynamics {
+        let ln_weights: Vec<f64> = self.ln_weights(1.0 / temperature, field).collect();
+        let largest = ln_weights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let (mut z, mut sums) = (0.0, [0.0; 5]);
+        for (&(energy, magnetization, _), ln_weight) in self.states.iter().zip(ln_weights) {
+            let weight = (ln_weight - largest).exp();
+            let energy = energy - field * magnetization;
+            z += weight;
+            let values = [energy, energy * energy, magnetization, magnetization.abs(), magnetization * magnetization];
+            for (sum, value) in sums.iter_mut().zip(values) {
+                *sum += weight * value;
+            }
+        }
+        let n = self.sites;
+        let [e, e2, m, abs_m, m2] = sums.map(|sum| sum / z);
+        Thermodynamics {
+            free_energy: f64::NAN,
+            energy: e / n,
+            magnetization: m / n,
+            absolute_magnetization: Some(abs_m / n),
+            specific_heat: exact::fluctuation(e2 - e * e, n * temperature * temperature),
+            susceptibility: exact::fluctuation(m2 - abs_m * abs_m, n * temperature),
+        }
+    }
+
+    /// Temperature within `range` at which `observable` peaks in field `field`, and its
+    /// value there, from a scan refined by golden-section search.
+    pub fn maximum(&self, field: f64, observable: impl Fn(&Thermodynamics) -> f64) -> (f64, f64) {
+        const SCAN: usize = 40;
+        let value = |temperature: f64| observable(&self.thermodynamics(temperature, field));
+        let (lowest, highest) = self.range();
+        let step = (highest - lowest) / SCAN as f64;
+        let best = (0..=SCAN)
+            .map(|k| lowest + step * k as f64)
+            .map(|temperature| (temperature, value(temperature)))
+            .fold((lowest, f64::NEG_INFINITY), |best, point| if point.1 > best.1 { point } else { best });
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let (mut a, mut b) = ((best.0 - step).max(lowest), (best.0 + step).min(highest));
+        while b - a > 1e-6 * highest {
+            let (c, d) = (b - ratio * (b - a), a + ratio * (b - a));
+            if value(c) > value(d) {
+                b = d;
+            } else {
+                a = c;
+            }
+        }
+        let temperature = (a + b) / 2.0;
+        (temperature, value(temperature))
+    }
+}
+
+fn log_sum_exp(values: impl Iterator<Item = f64> + Clone) -> f64 {
+    let largest = values.clone().fold(f64::NEG_INFINITY, f64::max);
+    largest + values.map(|value| (value - largest).exp()).sum::<f64>().ln()
+}
+
+/// Series recorded from the interactive view, one per temperature and field held long
+/// enough, all cleared when the couplings, fields or vacancies change.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    conditions: Vec<f64>,
+    /// (T, h) of the latest sample, and samples still to skip there.
+    current: (f64, f64),
+    settling: usize,
+    pub series: Vec<Series>,
+}
+
+impl Recorder {
+    /// Records `ising` once settled, returning whether it was.
+    pub fn add(&mut self, ising: &Ising) -> bool {
+        let mut conditions = exact::conditions(ising);
+        conditions.push(ising.crystal_field);
+        if conditions != self.conditions {
+            self.conditions = conditions;
+            self.series.clear();
+            self.settling = SETTLING;
+        }
+        let current = (ising.temperature, ising.magnetic_moment * ising.magnetic_field_strength);
+        if current != self.current {
+            self.current = current;
+            self.settling = SETTLING;
+        }
+        if self.settling > 0 || current.0 <= 0.0 {
+            self.settling = self.settling.saturating_sub(1);
+            return false;
+        }
+        let (temperature, field) = current;
+        let index = match self.series.iter().position(|s| s.temperature == temperature && s.field == field) {
+            Some(index) => index,
+            None => {
+                if self.series.len() == MAX_SERIES {
+                    self.series.remove(0);
+                }
+                self.series.push(Series { temperature, field, samples: Vec::new() });
+                self.series.len() - 1
+            }
+        };
+        let samples = &mut self.series[index].samples;
+        if samples.len() == SERIES_LENGTH {
+            return false;
+        }
+        samples.push(sample(ising));
+        true
+    }
+
+    pub fn samples(&self) -> usize {
+        self.series.iter().map(|s| s.samples.len()).sum()
+    }
+}
+
+/// Runs at a series of temperatures in zero field, recorded for reweighting, heating from
+/// the ordered state and carrying the configuration along.
+#[derive(Debug)]
+pub struct Recording {
+    /// Sweeps recorded at every temperature, one sample each, after a quarter as many to
+    /// equilibrate.
+    pub sweeps: usize,
+    pub algorithm: Algorithm,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            sweeps: 20000,
+            algorithm: Algorithm::SwendsenWang,
+        }
+    }
+}
+
+impl Recording {
+    pub fn run<R: Rng + ?Sized>(&self, ising: &mut Ising, temperatures: &[f64], rng: &mut R) -> Vec<Series> {
+        ising.magnetic_field_strength = 0.0;
+        for (spin, &occupied) in ising.lattice.iter_mut().zip(ising.occupancy.iter()) {
+            *spin = occupied as i8;
+        }
+        let num_sites = ising.lattice.len();
+        temperatures
+            .iter()
+            .map(|&temperature| {
+                ising.temperature = temperature;
+                for _ in 0..self.sweeps / 4 {
+                    self.algorithm.run(ising, num_sites, rng);
+                }
+                let samples = (0..self.sweeps)
+                    .map(|_| {
+                        self.algorithm.run(ising, num_sites, rng);
+                        sample(ising)
+                    })
+                    .collect();
+                Series { temperature, field: 0.0, samples }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exact::Enumeration;
+    use crate::lattice::Rectangular;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::rc::Rc;
+
+    fn record(size: usize, temperatures: &[f64], sweeps: usize) -> (Ising, Vec<Series>) {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut ising = Ising::new(Rc::new(Rectangular::square(size)), &mut rng);
+        let series = Recording { sweeps, ..Recording::default() }.run(&mut ising, temperatures, &mut rng);
+        (ising, series)
+    }
+
+    #[test]
+    fn reweights_a_series_to_its_own_means() {
+        let (_, series) = record(8, &[2.3], 2000);
+        let estimate = Reweighting::new(64, &series).unwrap().thermodynamics(2.3, 0.0);
+        let samples = &series[0].samples;
+        let mean = |f: &dyn Fn(&(f64, f64)) -> f64| samples.iter().map(f).sum::<f64>() / samples.len() as f64;
+        let (e, e2) = (mean(&|s| s.0), mean(&|s| s.0 * s.0));
+        let (m, abs_m) = (mean(&|s| s.1), mean(&|s| s.1.abs()));
+        assert!((estimate.energy - e / 64.0).abs() < 1e-9);
+        assert!((estimate.magnetization - m / 64.0).abs() < 1e-9);
+        assert!((estimate.absolute_magnetization.unwrap() - abs_m / 64.0).abs() < 1e-9);
+        assert!((estimate.specific_heat - (e2 - e * e) / (64.0 * 2.3 * 2.3)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn two_series_agree_with_enumeration() {
+        let (ising, series) = record(4, &[2.0, 3.0], 20000);
+        let reweighting = Reweighting::new(16, &series).unwrap();
+        let enumeration = Enumeration::new(&ising).unwrap();
+        let ln_z = |temperature: f64| -16.0 * enumeration.thermodynamics(temperature, 0.0).free_energy / temperature;
+        assert!((reweighting.runs[1].ln_z - (ln_z(3.0) - ln_z(2.0))).abs() < 0.1);
+        // Between the runs as well as at them, to within the errors of 20000 sweeps each
+        for temperature in [2.0, 2.5, 3.0] {
+            let estimate = reweighting.thermodynamics(temperature, 0.0);
+            let exact = enumeration.thermodynamics(temperature, 0.0);
+            assert!((estimate.energy - exact.energy).abs() < 0.05);
+            assert!((estimate.absolute_magnetization.unwrap() - exact.absolute_magnetization.unwrap()).abs() < 0.05);
+            assert!((estimate.specific_heat - exact.specific_heat).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn warm_start_matches_the_full_solve() {
+        let (_, mut series) = record(8, &[2.2, 2.3, 2.4], 2000);
+        let tail = series[2].samples.split_off(1500);
+        let previous = Reweighting::new(64, &series).unwrap();
+        series[2].samples.extend(tail);
+        let solved = Reweighting::new(64, &series).unwrap();
+        let mut warm = Reweighting::start(64, &series, Some(&previous)).unwrap();
+        assert!(warm.iterate(MAX_ITERATIONS, TOLERANCE));
+        for (warm, solved) in warm.runs.iter().zip(solved.runs.iter()) {
+            assert!((warm.ln_z - solved.ln_z).abs() < 1e-7);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+use std::fs;
+
+/// Shape of a temperature schedule.
+#[derive(Debug)]
+pub enum Profile {
+    /// Start from a random (T = ∞) configuration and hold the target temperature.
+    Quench { temperature: f64 },
+    Linear { start: f64, end: f64 },
+    Exponential { start: f64, end: f64 },
+    /// (frame, temperature) control points, linearly interpolated.
+    Piecewise(Vec<(usize, f64)>),
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Profile::Quench { temperature } => write!(f, "quench ∞ → {:.3}", temperature),
+            Profile::Linear { start, end } => write!(f, "linear {:.3} → {:.3}", start, end),
+            Profile::Exponential { start, end } => write!(f, "exp {:.3} → {:.3}", start, end),
+            Profile::Piecewise(points) => write!(f, "piecewise ({} points)", points.len()),
+        }
+    }
+}
+
+/// A temperature protocol followed frame by frame by the app.
+#[derive(Debug)]
+pub struct Schedule {
+    pub profile: Profile,
+    pub paused: bool,
+    length: usize,
+    frame: usize,
+}
+
+impl Schedule {
+    pub fn new(profile: Profile, length: usize) -> Self {
+        Self {
+            profile,
+            paused: false,
+            length: length.max(1),
+            frame: 0,
+        }
+    }
+
+    /// Parses a schedule spec of the form `quench:T:frames`, `linear:T0:T1:frames`,
+    /// `exp:T0:T1:frames` or `file:path`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, rest) = spec.split_once(':').unwrap_or((spec, ""));
+        if kind == "file" {
+            return Self::from_file(rest);
+        }
+
+        let fields: Vec<&str> = rest.split(':').collect();
+        let Some((frames, temperatures)) = fields.split_last() else {
+            return Err(format!("unrecognized schedule '{}'", spec));
+        };
+        let frames = frames
+            .parse::<usize>()
+            .map_err(|_| format!("invalid number of frames '{}' in schedule '{}'", frames, spec))?;
+        let temperatures = temperatures
+            .iter()
+            .map(|v| v.parse::<f64>().map_err(|_| format!("invalid number '{}' in schedule '{}'", v, spec)))
+            .collect::<Result<Vec<f64>, String>>()?;
+        if temperatures.iter().any(|&temperature| temperature <= 0.0) {
+            return Err(format!("schedule '{}' needs positive temperatures", spec));
+        }
+
+        match (kind, temperatures.as_slice()) {
+            ("quench", &[temperature]) => Ok(Self::new(Profile::Quench { temperature }, frames)),
+            ("linear", &[start, end]) => Ok(Self::new(Profile::Linear { start, end }, frames)),
+            ("exp", &[start, end]) => Ok(Self::new(Profile::Exponential { start, end }, frames)),
+            _ => Err(format!("unrecognized schedule '{}'", spec)),
+        }
+    }
+
+    /// Reads a piecewise schedule, one `frame temperature` pair per line. Blank lines and
+    /// lines starting with `#` are ignored.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut points = Vec::new();
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let point = match fields.as_slice() {
+                [frame, temperature] => frame.parse::<usize>().ok().zip(temperature.parse::<f64>().ok()),
+                _ => None,
+            };
+            match point {
+                Some((_, temperature)) if temperature <= 0.0 => {
+                    return Err(format!("{}:{}: temperatures must be positive", path, n + 1))
+                }
+                Some(point) => points.push(point),
+                None => return Err(format!("{}:{}: expected 'frame temperature'", path, n + 1)),
+            }
+        }
+
+        if points.is_empty() {
+            return Err(format!("{}: schedule has no points", path));
+        }
+        points.sort_by_key(|&(frame, _)| frame);
+        let length = points[points.len() - 1].0;
+
+        Ok(Self::new(Profile::Piecewise(points), length))
+    }
+
+    pub fn temperature(&self) -> f64 {
+        let t = self.progress();
+        match &self.profile {
+            Profile::Quench { temperature } => *temperature,
+            Profile::Linear { start, end } => start + (end - start) * t,
+            Profile::Exponential { start, end } => start * (end / start).powf(t),
+            Profile::Piecewise(points) => {
+                let after = points.iter().position(|&(frame, _)| frame > self.frame);
+                match after {
+                    None => points[points.len() - 1].1,
+                    Some(0) => points[0].1,
+                    Some(i) => {
+                        let (f0, t0) = points[i - 1];
+                        let (f1, t1) = points[i];
+                        t0 + (t1 - t0) * (self.frame - f0) as f64 / (f1 - f0) as f64
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn advance(&mut self) {
+        if !self.paused && !self.is_finished() {
+            self.frame += 1;
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.paused = false;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.length
+    }
+
+    pub fn is_quench(&self) -> bool {
+        matches!(self.profile, Profile::Quench { .. })
+    }
+
+    pub fn progress(&self) -> f64 {
+        self.frame as f64 / self.length as f64
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
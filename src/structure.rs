@@ -0,0 +1,51 @@
+use std::f64::consts::PI;
+
+use crate::ising::Ising;
+
+/// Wavevector q maximizing the structure factor S(q) = |Σ_j σ_j exp(-i q·r_j)|² / N, over
+/// the pixel positions r_j of the drawing and the wavevectors it resolves. Components are in
+/// units of π, with q_x in [0, 1] and q_y in (-1, 1], since S(q) = S(-q). Ferromagnetic
+/// order peaks at q = 0, and stripes of period p along x at q_x = 2 / p.
+pub fn dominant_wavevector(ising: &Ising) -> (f64, f64) {
+    let canvas = ising.canvas(0);
+    let (width, height) = (canvas.width, canvas.height);
+    let spin = |x: usize, y: usize| canvas.sites[y * width + x].map_or(0.0, |idx| ising.lattice[idx] as f64);
+
+    // Partial transforms along x of every row, then along y for every q_x
+    let phases = |n: usize| -> Vec<(f64, f64)> {
+        (0..n)
+            .map(|k| {
+                let angle = 2.0 * PI * k as f64 / n as f64;
+                (angle.cos(), -angle.sin())
+            })
+            .collect()
+    };
+    let (x_phases, y_phases) = (phases(width), phases(height));
+
+    let mut best = (0.0, (0, 0));
+    for qx in 0..=width / 2 {
+        let rows: Vec<(f64, f64)> = (0..height)
+            .map(|y| {
+                (0..width).fold((0.0, 0.0), |(re, im), x| {
+                    let (c, s) = x_phases[(qx * x) % width];
+                    let sigma = spin(x, y);
+                    (re + sigma * c, im + sigma * s)
+                })
+            })
+            .collect();
+        for qy in 0..height {
+            let (re, im) = rows.iter().enumerate().fold((0.0, 0.0), |(re, im), (y, &(a, b))| {
+                let (c, s) = y_phases[(qy * y) % height];
+                (re + a * c - b * s, im + a * s + b * c)
+            });
+            let intensity = re * re + im * im;
+            if intensity > best.0 {
+                best = (intensity, (qx, qy));
+            }
+        }
+    }
+
+    let (qx, qy) = best.1;
+    let qy = if 2 * qy > height { qy as f64 - height as f64 } else { qy as f64 };
+    (2.0 * qx as f64 / width as f64, 2.0 * qy / height as f64)
+}
@@ -0,0 +1,225 @@
+use rand::Rng;
+
+use crate::anneal::Solution;
+use crate::cluster::Algorithm;
+use crate::ising::Ising;
+
+/// Replica exchange: copies of one system held at a ladder of temperatures, with
+/// configurations swapped between neighbouring temperatures.
+#[derive(Debug)]
+pub struct ParallelTempering {
+    /// Replica `i` is always simulated at `temperatures[i]`.
+    pub replicas: Vec<Ising>,
+    pub temperatures: Vec<f64>,
+    pub algorithm: Algorithm,
+    energies: Vec<f64>,
+    /// Swap attempts and accepted swaps between temperatures `i` and `i + 1`.
+    attempts: Vec<usize>,
+    accepted: Vec<usize>,
+    /// Walker, one per configuration, whose configuration replica `i` holds.
+    walkers: Vec<usize>,
+    /// Per walker, the hottest temperature reached since it was last at the coldest, and
+    /// the sweep it arrived there, if ever.
+    journeys: Vec<(usize, Option<usize>)>,
+    /// Per temperature above the coldest, the number and total length in sweeps of round
+    /// trips from the coldest to it and back.
+    round_trips: Vec<(usize, usize)>,
+    sweeps: usize,
+}
+
+impl ParallelTempering {
+    pub fn new<R: Rng + ?Sized>(ising: &Ising, temperatures: Vec<f64>, rng: &mut R) -> Self {
+        let replicas: Vec<Ising> = temperatures
+            .iter()
+            .map(|&temperature| {
+                let mut replica = ising.clone();
+                replica.temperature = temperature;
+                replica.randomize(rng);
+                replica
+            })
+            .collect();
+        let energies = replicas.iter().map(Ising::total_energy).collect();
+        let count = replicas.len();
+
+        Self {
+            replicas,
+            temperatures,
+            algorithm: Algorithm::Metropolis,
+            energies,
+            attempts: vec![0; count.saturating_sub(1)],
+            accepted: vec![0; count.saturating_sub(1)],
+            walkers: (0..count).collect(),
+            journeys: (0..count).map(|walker| (0, (walker == 0).then_some(0))).collect(),
+            round_trips: vec![(0, 0); count.saturating_sub(1)],
+            sweeps: 0,
+        }
+    }
+
+    /// Gives every replica the couplings, fields and disorder of `ising`, which may have
+    /// changed. Sites turned into vacancies are emptied, and those filled start up.
+    pub fn sync(&mut self, ising: &Ising) {
+        for (replica, energy) in self.replicas.iter_mut().zip(self.energies.iter_mut()) {
+            let sites = replica.lattice.iter_mut().zip(replica.occupancy.iter_mut());
+            for ((spin, occupied), &now) in sites.zip(ising.occupancy.iter()) {
+                if *occupied != now {
+                    *occupied = now;
+                    *spin = now as i8;
+                }
+            }
+            for (bonds, source) in replica.bonds.iter_mut().zip(ising.bonds.iter()) {
+                bonds.copy_from_slice(source);
+            }
+            replica.horizontal_coupling = ising.horizontal_coupling;
+            replica.vertical_coupling = ising.vertical_coupling;
+            replica.diagonal_coupling = ising.diagonal_coupling;
+            replica.axial_coupling = ising.axial_coupling;
+            replica.crystal_field = ising.crystal_field;
+            replica.magnetic_moment = ising.magnetic_moment;
+            replica.magnetic_field_strength = ising.magnetic_field_strength;
+            replica.fields.copy_from_slice(&ising.fields);
+            *energy = replica.total_energy();
+        }
+    }
+
+    pub fn randomize<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for (replica, energy) in self.replicas.iter_mut().zip(self.energies.iter_mut()) {
+            replica.randomize(rng);
+            *energy = replica.total_energy();
+        }
+    }
+
+    /// `count` temperatures spaced geometrically from `t_min` to `t_max`.
+    pub fn geometric_ladder(t_min: f64, t_max: f64, count: usize) -> Vec<f64> {
+        let count = count.max(2);
+        (0..count)
+            .map(|i| t_min * (t_max / t_min).powf(i as f64 / (count - 1) as f64))
+            .collect()
+    }
+
+    /// One sweep of every replica followed by a round of swap attempts.
+    pub fn sweep<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for (replica, energy) in self.replicas.iter_mut().zip(self.energies.iter_mut()) {
+            let num_sites = replica.lattice.len();
+            match self.algorithm {
+                Algorithm::Metropolis => {
+                    for _ in 0..num_sites {
+                        *energy += replica.monte_carlo_step(rng);
+                    }
+                }
+                algorithm => {
+                    algorithm.run(replica, num_sites, rng);
+                    *energy = replica.total_energy();
+                }
+            }
+        }
+        self.attempt_swaps(rng);
+        self.sweeps += 1;
+        self.follow_walkers();
+    }
+
+    /// Metropolis replica exchange between neighbouring temperatures, accepted with
+    /// probability min(1, exp[(β_i - β_j)(E_i - E_j)]).
+    fn attempt_swaps<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for i in 0..self.replicas.len() - 1 {
+            let delta = (1.0 / self.temperatures[i] - 1.0 / self.temperatures[i + 1])
+                * (self.energies[i] - self.energies[i + 1]);
+            self.attempts[i] += 1;
+            if delta >= 0.0 || rng.gen::<f64>() < delta.exp() {
+                let (low, high) = self.replicas.split_at_mut(i + 1);
+                std::mem::swap(&mut low[i].lattice, &mut high[0].lattice);
+                self.energies.swap(i, i + 1);
+                self.walkers.swap(i, i + 1);
+                self.accepted[i] += 1;
+            }
+        }
+    }
+
+    /// Times each walker from its arrival at the coldest temperature until it returns,
+    /// counting a round trip to every temperature it reached on the way. Swaps move a
+    /// walker down at most one temperature per sweep, so it is seen at the coldest on
+    /// every return.
+    fn follow_walkers(&mut self) {
+        for (i, &walker) in self.walkers.iter().enumerate() {
+            let hottest = &mut self.journeys[walker].0;
+            *hottest = (*hottest).max(i);
+        }
+        let journey = &mut self.journeys[self.walkers[0]];
+        match *journey {
+            (0, Some(_)) => {}
+            (hottest, Some(start)) => {
+                for (count, total) in self.round_trips[..hottest].iter_mut() {
+                    *count += 1;
+                    *total += self.sweeps - start;
+                }
+                *journey = (0, Some(self.sweeps));
+            }
+            (_, None) => *journey = (0, Some(self.sweeps)),
+        }
+    }
+
+    /// Fraction of accepted swaps between each pair of neighbouring temperatures.
+    pub fn acceptance_rates(&self) -> Vec<f64> {
+        self.accepted
+            .iter()
+            .zip(self.attempts.iter())
+            .map(|(&accepted, &attempts)| accepted as f64 / attempts.max(1) as f64)
+            .collect()
+    }
+
+    /// Per temperature above the coldest, the number of round trips from the coldest to
+    /// it and back, and their mean length in sweeps, if any. A trip starts when a walker
+    /// arrives at the coldest temperature and ends when it next returns there. The last
+    /// entry counts the full trips through the hottest temperature.
+    pub fn round_trips(&self) -> Vec<(usize, Option<f64>)> {
+        self.round_trips
+            .iter()
+            .map(|&(count, total)| (count, (count > 0).then(|| total as f64 / count as f64)))
+            .collect()
+    }
+
+    /// Runs `sweeps` sweeps and returns the lowest energy configuration seen, polished by
+    /// a zero temperature descent.
+    pub fn solve<R: Rng + ?Sized>(&mut self, sweeps: usize, rng: &mut R) -> Solution {
+        let mut best = Solution {
+            energy: f64::INFINITY,
+            spins: self.replicas[0].lattice.clone(),
+        };
+        for _ in 0..sweeps {
+            self.sweep(rng);
+            for (replica, &energy) in self.replicas.iter().zip(self.energies.iter()) {
+                best.update(energy, &replica.lattice);
+            }
+        }
+
+        let mut ising = self.replicas[0].clone();
+        ising.lattice.copy_from_slice(&best.spins);
+        ising.descend();
+        best.energy = ising.total_energy();
+        best.spins = ising.lattice;
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lattice::Rectangular;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::rc::Rc;
+
+    #[test]
+    fn times_round_trips_from_the_arrival_at_the_coldest() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let ising = Ising::new(Rc::new(Rectangular::square(4)), &mut rng);
+        let mut tempering = ParallelTempering::new(&ising, vec![1.0, 2.0, 3.0], &mut rng);
+        // The walker at each temperature, as walker 0 waits at the coldest, climbs to the
+        // hottest and comes back down
+        let positions = [[0, 1, 2]; 5].into_iter().chain([[1, 0, 2], [1, 2, 0], [1, 0, 2], [0, 1, 2]]);
+        for walkers in positions {
+            tempering.walkers = walkers.to_vec();
+            tempering.sweeps += 1;
+            tempering.follow_walkers();
+        }
+        assert_eq!(tempering.round_trips(), vec![(1, Some(9.0)), (1, Some(9.0))]);
+    }
+}
@@ -1,24 +1,32 @@
-use crate::app::{App, MCOrder, Page};
+use crate::app::{App, MCOrder, Page, View};
+use crate::ising::{Ising, Model};
+use crate::onsager::{self, Torus};
 use crate::parameter::ParameterType;
+use crate::tempering::ParallelTempering;
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
 
 use ratatui::widgets::Padding;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
-    text::Text,
-    widgets::{Block, Borders, Gauge, Paragraph, Clear},
+    symbols::Marker,
+    text::{Line, Span, Text},
+    widgets::{Axis, Bar, BarChart, BarGroup, Block, Borders, Chart, Clear, Dataset, Gauge, GraphType, Paragraph},
     Frame,
 };
 
 pub fn ui(frame: &mut Frame, app: &mut App) {
-    let x_size = app.ising.size as u16 / 2;
-    let y_size = app.ising.size as u16 / 4;
+    let (width, height) = app.ising.canvas_size();
+    let x_size = width.div_ceil(2) as u16;
+    let y_size = height.div_ceil(4) as u16;
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3),
-            Constraint::Length(y_size + 1),
+            Constraint::Min(y_size + 1),
             Constraint::Length(1),
             Constraint::Length(1),
         ])
@@ -52,82 +60,571 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         // .borders(Borders::ALL)
         .style(Style::default());
 
-    let ising_canvas = Paragraph::new(app.ising.lattice_as_braille())
+    let lattice_text = match (&app.view, &app.planar, &app.heisenberg) {
+        (_, Some(planar), _) => lattice_as_blocks(&app.ising, app.slice, 1, |idx| hue_color(planar.angles[idx] / (2.0 * PI))),
+        (View::Angle, _, Some(heisenberg)) => lattice_as_blocks(&app.ising, app.slice, 1, |idx| {
+            let [x, y, _] = heisenberg.spins[idx];
+            hue_color(y.atan2(x) / (2.0 * PI))
+        }),
+        (_, _, Some(heisenberg)) => lattice_as_blocks(&app.ising, app.slice, 1, |idx| {
+            let shade = ((heisenberg.spins[idx][2] + 1.0) / 2.0 * 255.0) as u8;
+            Color::Rgb(shade, shade, 255)
+        }),
+        (View::Braille, _, _) => Text::from(app.ising.lattice_as_braille(app.slice)),
+        _ => lattice_as_blocks(&app.ising, app.slice, 1, |idx| spin_color(app.ising.model, app.ising.lattice[idx])),
+    };
+    let ising_canvas = Paragraph::new(lattice_text)
         .block(ising_block)
         .alignment(Alignment::Center);
+    // Without a layout, the color view is a histogram of degrees instead
+    let has_layout = app.ising.geometry.canvas_size() != (0, 0);
+    let degree_chart = (app.view == View::Color && !has_layout && app.has_ising_spins()).then(|| {
+        let bars: Vec<Bar> = degree_classes(&app.ising)
+            .into_iter()
+            .map(|(label, count, magnetization)| {
+                let shade = ((magnetization + 1.0) / 2.0 * 255.0) as u8;
+                // Log heights, so heavy tailed degree distributions stay readable
+                Bar::default()
+                    .label(Line::from(label))
+                    .value(((count as f64).ln_1p() * 100.0) as u64)
+                    .text_value(count.to_string())
+                    .style(Style::default().fg(Color::Rgb(shade, shade, 255)))
+            })
+            .collect();
+        BarChart::default()
+            .block(
+                Block::default()
+                    .style(Style::default().fg(Color::White))
+                    .borders(Borders::ALL)
+                    .title(" Sites per degree (log scale), shaded by magnetization "),
+            )
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(3)
+            .bar_gap(1)
+    });
 
     //
     // Controls
     //
 
+    let magnetization_label = match app.ising.model {
+        Model::Ising | Model::BlumeCapel => "Magnetization (m):",
+        Model::Potts { .. } => "Order parameter:  ",
+    };
+    let update = match (&app.planar, &app.heisenberg) {
+        (Some(planar), _) if planar.overrelaxation && planar.clock.is_none() => "Metropolis + overrelaxation".to_string(),
+        (_, Some(heisenberg)) if heisenberg.heat_bath => "Heat bath".to_string(),
+        (Some(_), _) | (_, Some(_)) => "Metropolis".to_string(),
+        _ => app.algorithm.to_string(),
+    };
+    let mut observables = vec![
+        format!("{} {:>8.4}", magnetization_label, app.magnetization),
+        format!("Energy (E/N):      {:>8.4}", app.energy),
+        format!("Update:            {:>8}", update),
+    ];
+    if app.ising.model == Model::BlumeCapel {
+        observables.push(format!("Density ⟨σ²⟩:      {:>8.4}", app.ising.quadrupole_moment()));
+    }
+    let (horizontal, vertical) = (app.ising.horizontal_coupling, app.ising.vertical_coupling);
+    let geometry = &app.ising.geometry;
+    // The replica is kept only with bond disorder, which may draw unit weights
+    let clean = app.ising.is_clean() && app.replica.is_none();
+    if let Some(planar) = &app.planar {
+        let stiffness = 2.0 * app.ising.temperature / PI;
+        observables.push(format!("Vortex density:    {:>8.4}", planar.vortex_density(&app.ising)));
+        observables.push(format!(
+            "Helicity (Υ):      {:>8.4} (2T/π = {:.4})",
+            planar.helicity_modulus(&app.ising),
+            stiffness
+        ));
+        match planar.critical_temperature(&app.ising) {
+            Some((critical_temperature, true)) => observables.push(format!("Exact T_c:         {:>8.4}", critical_temperature)),
+            Some((critical_temperature, false)) => observables.push(format!("T_BKT (Monte Carlo): {:>6.4}", critical_temperature)),
+            None => {}
+        }
+    } else if let Some(heisenberg) = &app.heisenberg {
+        observables.push(format!("Magnetization (m_z): {:>6.4}", heisenberg.mean_spin(&app.ising)[2]));
+        match heisenberg.critical_temperature(&app.ising) {
+            Some(0.0) => observables.push("Exact T_c:         none (Mermin-Wagner)".to_string()),
+            Some(critical_temperature) => observables.push(format!("T_c (Monte Carlo): {:>8.4}", critical_temperature)),
+            None => {}
+        }
+    } else if !clean {
+        // Dilution lowers T_c from the pure lattice value, down to 0 at the percolation
+        // threshold, and a 2D spin glass orders only at T = 0
+    } else if let Model::Potts { states } = app.ising.model {
+        if let Some(critical_temperature) = geometry.potts_critical_temperature(horizontal, vertical, states as usize) {
+            let order = if states > 4 { "1st order" } else { "continuous" };
+            observables.push(format!("Exact T_c:         {:>8.4} ({})", critical_temperature, order));
+        }
+    } else if app.ising.model == Model::BlumeCapel {
+        // Unsolved, and the crystal field moves T_c
+    } else if (horizontal < 0.0 || vertical < 0.0) && geometry.is_frustrated() {
+        observables.push("Exact T_c:         none (frustrated)".to_string());
+    } else if let Some(critical_temperature) = geometry.critical_temperature(horizontal, vertical) {
+        let label = if geometry.is_exactly_solved() { "Exact T_c:        " } else { "T_c (Monte Carlo):" };
+        observables.push(format!("{} {:>8.4}", label, critical_temperature));
+    }
+    // Kaufman's torus and Onsager's infinite lattice, unless enumeration is exact already
+    let torus = app.has_ising_spins().then(|| Torus::of(&app.ising)).flatten();
+    let isotropic = horizontal.abs() == vertical.abs();
+    let ferromagnetic = horizontal > 0.0 && vertical > 0.0;
+    if let (Some(torus), None) = (&torus, &app.exact) {
+        let temperature = app.ising.temperature;
+        let infinite = |value: f64| if isotropic { format!(" (∞ {:.4})", value) } else { String::new() };
+        observables.push(format!(
+            "Exact E/N:         {:>8.4}{}",
+            torus.energy(temperature),
+            infinite(onsager::energy(horizontal, temperature))
+        ));
+        observables.push(format!(
+            "Exact C:           {:>8.4}{}",
+            torus.specific_heat(temperature),
+            infinite(onsager::specific_heat(horizontal, temperature))
+        ));
+        if ferromagnetic {
+            let magnetization = onsager::spontaneous_magnetization(horizontal, vertical, temperature);
+            observables.push(format!("Exact |m| (∞):     {:>8.4}", magnetization));
+        }
+    }
+    if let Some(exact) = &app.exact {
+        let field = app.ising.magnetic_moment * app.ising.magnetic_field_strength;
+        let exact = exact.thermodynamics(app.ising.temperature, field);
+        let mc = app.sampler.thermodynamics(&app.ising);
+        let nan = f64::NAN;
+        observables.push(format!("Exact E/N:         {:>8.4} (MC {:.4})", exact.energy, mc.energy));
+        observables.push(format!(
+            "Exact |m|:         {:>8.4} (MC {:.4})",
+            exact.absolute_magnetization.unwrap_or(nan),
+            mc.absolute_magnetization.unwrap_or(nan)
+        ));
+        observables.push(format!("Exact C:           {:>8.4} (MC {:.4})", exact.specific_heat, mc.specific_heat));
+        observables.push(format!(
+            "Exact χ:           {:>8.4} (MC {:.4}, {} samples)",
+            exact.susceptibility,
+            mc.susceptibility,
+            app.sampler.samples()
+        ));
+    }
+    if let Some((qx, qy)) = app.modulation {
+        observables.push(format!("Modulation q/π:    ({:.3}, {:.3})", qx, qy));
+    }
+    let depth = app.ising.geometry.depth();
+    if depth > 1 {
+        observables.push(format!("Slice (z):         {:>8}", format!("{}/{}", app.slice, depth)));
+    }
+    if app.dilution > 0.0 {
+        let occupied = app.ising.num_occupied() as f64 / app.ising.lattice.len() as f64;
+        observables.push(format!("Occupied sites:    {:>8.4}", occupied));
+    }
+    if app.replica.is_some() {
+        observables.push(format!("Overlap (q):       {:>8.4}", app.overlap));
+    }
+    let wang_landau = app.density_of_states.as_ref().map(|density_of_states| density_of_states.thermodynamics(app.ising.temperature));
+    if let (Some(density_of_states), Some(estimate)) = (&app.density_of_states, &wang_landau) {
+        let state = if density_of_states.is_converged() { "converged".to_string() } else { format!("{} refinements", density_of_states.refinements()) };
+        observables.push(format!("Wang-Landau ln f:  {:>8.1e} ({})", density_of_states.ln_f(), state));
+        observables.push(format!(
+            "Flatness:          {:>8.4} (flat at {})",
+            density_of_states.flatness(),
+            density_of_states.settings.flatness
+        ));
+        observables.push(format!("Sweeps:            {:>8}", density_of_states.sweeps()));
+        observables.push(format!("g(E) F/N:          {:>8.4}", estimate.free_energy));
+        observables.push(format!("g(E) E/N:          {:>8.4}", estimate.energy));
+        observables.push(format!("g(E) C:            {:>8.4}", estimate.specific_heat));
+        if let Some(magnetization) = estimate.absolute_magnetization {
+            observables.push(format!("g(E) |m|:          {:>8.4}", magnetization));
+        }
+        if let Some(export) = &app.export {
+            observables.push(export.clone());
+        }
+    }
+    if let Some(tempering) = &app.tempering {
+        let round_trips = tempering.round_trips();
+        observables.push(format!("Replicas:          {:>8} (coldest shown)", tempering.replicas.len()));
+        // Through the hottest, then to each temperature from the coldest and back
+        if let Some(&(count, mean)) = round_trips.last() {
+            match mean {
+                Some(mean) => observables.push(format!("Round trips:       {:>8} (mean {:.1} sweeps)", count, mean)),
+                None => observables.push(format!("Round trips:       {:>8}", count)),
+            }
+        }
+        let means: Vec<String> = round_trips
+            .iter()
+            .map(|(_, mean)| mean.map_or("-".to_string(), |mean| format!("{:.0}", mean)))
+            .collect();
+        observables.push(format!("Mean trip to T_k:  {}", means.join(" ")));
+    }
+    if !app.reweighted.is_empty() {
+        observables.push(format!(
+            "Reweighted series: {:>8} ({} samples)",
+            app.recorder.series.len(),
+            app.recorder.samples()
+        ));
+        if let Some((temperature, susceptibility)) = app.susceptibility_peak {
+            observables.push(format!("χ peak (reweight): {:>8.4} (χ = {:.4})", temperature, susceptibility));
+        }
+        if let (None, Some(export)) = (&app.density_of_states, &app.export) {
+            observables.push(export.clone());
+        }
+    }
+
+    let parameters = app.parameters();
+    let mut constraints: Vec<Constraint> = parameters.iter().map(|_| Constraint::Length(5)).collect();
+    constraints.extend([
+        Constraint::Length(observables.len() as u16 + 2),
+        Constraint::Length(if app.schedule.is_some() { 3 } else { 0 }),
+        Constraint::Length(if app.replica.is_some() { 10 } else { 0 }),
+        Constraint::Length(if app.field_sweep.is_some() { 10 } else { 0 }),
+        Constraint::Length(if app.tempering.is_some() { 10 } else { 0 }),
+        Constraint::Length(if app.density_of_states.is_some() { 12 } else { 0 }),
+        Constraint::Min(1),
+    ]);
+
     let controls_chunk = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(5),
-            Constraint::Length(5),
-            Constraint::Length(5),
-            Constraint::Length(5),
-            Constraint::Min(1),
-        ])
+        .constraints(constraints)
         .split(sim_chunks[1]);
+    let panels_chunk = &controls_chunk[parameters.len()..];
 
-    let mut temp_control = Gauge::default()
-        .block(
-            Block::default()
-                .style(Style::default().fg(Color::White))
-                .borders(Borders::ALL)
-                .title(" Temperature (T) "),
-        )
-        .gauge_style(Style::default().bg(Color::DarkGray).fg(Color::Red))
-        .percent((app.temp_param.normalized() * 100.0) as u16)
-        .label(format!("{:.3}", app.ising.temperature));
+    //
+    // Observables
+    //
 
-    let mut coupling_control = Gauge::default()
-        .block(
-            Block::default()
-                .style(Style::default().fg(Color::White))
-                .borders(Borders::ALL)
-                .title(" Coupling constant (J) "),
-        )
-        .gauge_style(Style::default().bg(Color::DarkGray).fg(Color::LightGreen))
-        .percent((app.coupling_param.normalized() * 100.0) as u16)
-        .label(format!("{:.3}", app.ising.coupling_constant));
+    let stats = Paragraph::new(Text::from(observables.join("\n")))
+    .block(
+        Block::default()
+            .style(Style::default().fg(Color::White))
+            .padding(Padding::horizontal(1))
+            .borders(Borders::ALL)
+            .title(" Observables "),
+    );
 
-    let mut mag_moment_control = Gauge::default()
-        .block(
-            Block::default()
-                .style(Style::default().fg(Color::White))
-                .borders(Borders::ALL)
-                .title(" Magnetic moment (μ) "),
-        )
-        .gauge_style(Style::default().bg(Color::DarkGray).fg(Color::LightMagenta))
-        .percent((app.mag_moment_param.normalized() * 100.0) as u16)
-        .label(format!("{:.3}", app.ising.magnetic_moment));
+    //
+    // Temperature schedule
+    //
+
+    let schedule_progress = app.schedule.as_ref().map(|schedule| {
+        let state = if schedule.is_finished() {
+            " (done)"
+        } else if schedule.paused {
+            " (paused)"
+        } else {
+            ""
+        };
+        Gauge::default()
+            .block(
+                Block::default()
+                    .style(Style::default().fg(Color::White))
+                    .borders(Borders::ALL)
+                    .title(" Schedule "),
+            )
+            .gauge_style(Style::default().bg(Color::DarkGray).fg(Color::Blue))
+            .ratio(schedule.progress().min(1.0))
+            .label(format!(
+                "{}  {}/{}{}",
+                schedule.profile,
+                schedule.frame(),
+                schedule.length(),
+                state
+            ))
+    });
 
-    let mut mag_field_strength_control = Gauge::default()
+    //
+    // Overlap distribution
+    //
+
+    let overlap_percentages = app.overlap_histogram.percentages();
+    let overlap_bars: Vec<(&str, u64)> = overlap_percentages.iter().map(|&p| ("", p)).collect();
+    let overlap_chart = BarChart::default()
         .block(
             Block::default()
                 .style(Style::default().fg(Color::White))
                 .borders(Borders::ALL)
-                .title(" Magnetic field strength (B) "),
+                .title(" Overlap distribution P(q), q ∈ [-1, 1] (%) "),
         )
-        .gauge_style(Style::default().bg(Color::DarkGray).fg(Color::Cyan))
-        .percent((app.mag_field_strength_param.normalized() * 100.0) as u16)
-        .label(format!("{:.3}", app.ising.magnetic_field_strength));
+        .data(&overlap_bars)
+        .bar_width(3)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Color::LightYellow))
+        .value_style(Style::default().fg(Color::Black).bg(Color::LightYellow));
+
+    //
+    // Avalanches
+    //
+
+    let avalanche_chart = app.field_sweep.as_ref().map(|field_sweep| {
+        let bars: Vec<Bar> = field_sweep
+            .size_counts
+            .iter()
+            .map(|&count| Bar::default().value(count).text_value(String::new()))
+            .collect();
+        BarChart::default()
+            .block(
+                Block::default()
+                    .style(Style::default().fg(Color::White))
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        " Avalanche sizes, bins of 2^k  (last {}, field {} {:.3}) ",
+                        field_sweep.last_size,
+                        if field_sweep.direction > 0 { "↑" } else { "↓" },
+                        app.ising.magnetic_field_strength
+                    )),
+            )
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(3)
+            .bar_gap(1)
+            .bar_style(Style::default().fg(Color::LightCyan))
+    });
 
-    let selected_block = Block::default().borders(Borders::ALL).style(Style::default().fg(Color::Yellow));
-    match app.current_parameter {
-        ParameterType::Temp => temp_control = temp_control.block(selected_block.title(" Temperature (T) ")),
-        ParameterType::Coupling => coupling_control = coupling_control.block(selected_block.title(" Coupling constant (J) ")),
-        ParameterType::MagMoment => mag_moment_control = mag_moment_control.block(selected_block.title(" Magnetic moment (μ) ")),
-        ParameterType::MagFieldStrength => mag_field_strength_control = mag_field_strength_control.block(selected_block.title(" Magnetic field strength (B) ")),
+    //
+    // Swap acceptance
+    //
+
+    let acceptance_chart = app.tempering.as_ref().map(|tempering| {
+        let rates = tempering.acceptance_rates();
+        // Narrower bars for long ladders, to fit the panel
+        let bar_width = (sim_chunks[1].width.saturating_sub(2) as usize / rates.len()).saturating_sub(1).clamp(1, 4);
+        let bars: Vec<Bar> = rates
+            .iter()
+            .map(|&rate| {
+                let percent = (rate * 100.0).round() as u64;
+                Bar::default().value(percent).text_value(if bar_width >= 2 { percent.to_string() } else { String::new() })
+            })
+            .collect();
+        BarChart::default()
+            .block(
+                Block::default()
+                    .style(Style::default().fg(Color::White))
+                    .borders(Borders::ALL)
+                    .title(" Swap acceptance per temperature pair, coldest first (%) "),
+            )
+            .data(BarGroup::default().bars(&bars))
+            .max(100)
+            .bar_width(bar_width as u16)
+            .bar_gap(1)
+            .bar_style(Style::default().fg(Color::LightRed))
+            .value_style(Style::default().fg(Color::Black).bg(Color::LightRed))
+    });
+
+    //
+    // Wang-Landau histogram and density of states
+    //
+
+    let (visits, ln_g, flat) = match &app.density_of_states {
+        Some(density_of_states) => {
+            let histogram = density_of_states.histogram();
+            let n = density_of_states.walker.num_occupied() as f64;
+            let mean = histogram.iter().map(|p| p.1 as f64).sum::<f64>() / histogram.len() as f64;
+            let visits: Vec<(f64, f64)> = histogram.iter().map(|&(e, v)| (e / n, v as f64 / mean.max(1.0))).collect();
+            let ln_g: Vec<(f64, f64)> = density_of_states.ln_g().iter().map(|&(e, ln_g)| (e / n, ln_g / n)).collect();
+            let (first, last) = (visits[0].0, visits[visits.len() - 1].0);
+            let flat = vec![(first, density_of_states.settings.flatness), (last, density_of_states.settings.flatness)];
+            (visits, ln_g, flat)
+        }
+        None => (Vec::new(), Vec::new(), Vec::new()),
     };
+    let wang_landau_charts = app.density_of_states.as_ref().map(|_| {
+        let bounds = |points: &[(f64, f64)], axis: fn(&(f64, f64)) -> f64| {
+            let (lo, hi) = points.iter().map(axis).fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| (lo.min(x), hi.max(x)));
+            if lo < hi { [lo, hi] } else { [lo - 1.0, lo + 1.0] }
+        };
+        let energy_axis = |points: &[(f64, f64)]| {
+            let [lo, hi] = bounds(points, |p| p.0);
+            Axis::default()
+                .title("E/N")
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([lo, hi])
+                .labels(vec![Span::raw(format!("{:.2}", lo)), Span::raw(format!("{:.2}", hi))])
+        };
+        let line = |color: Color, points| {
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(color))
+                .data(points)
+        };
+        let highest_visits = bounds(&visits, |p| p.1)[1].max(1.5);
+        let histogram_chart = Chart::new(vec![line(Color::LightCyan, &visits), line(Color::DarkGray, &flat)])
+            .block(
+                Block::default()
+                    .style(Style::default().fg(Color::White))
+                    .borders(Borders::ALL)
+                    .title(" Visits H(E) / mean since refinement "),
+            )
+            .x_axis(energy_axis(&visits))
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().fg(Color::DarkGray))
+                    .bounds([0.0, highest_visits])
+                    .labels(vec![Span::raw("0"), Span::raw(format!("{:.1}", highest_visits))]),
+            );
+        let [lowest, highest] = bounds(&ln_g, |p| p.1);
+        let ln_g_chart = Chart::new(vec![line(Color::LightGreen, &ln_g)])
+            .block(
+                Block::default()
+                    .style(Style::default().fg(Color::White))
+                    .borders(Borders::ALL)
+                    .title(" ln g(E) / N "),
+            )
+            .x_axis(energy_axis(&ln_g))
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().fg(Color::DarkGray))
+                    .bounds([lowest, highest])
+                    .labels(vec![Span::raw(format!("{:.2}", lowest)), Span::raw(format!("{:.2}", highest))]),
+            );
+        (histogram_chart, ln_g_chart)
+    });
 
     //
     // Magnetization and Energy charts
     //
 
-    // let magnetization_chart = Chart::new(vec![Dataset::default().data(&[(0.0, 0.0), (10.0, 10.0)])]).block(Block::default().title("test"));
+    let magnetization_points: Vec<(f64, f64)> = app.history.iter().map(|&(t, m, _)| (t, m.abs())).collect();
+    let energy_points: Vec<(f64, f64)> = app.history.iter().map(|&(t, _, e)| (t, e)).collect();
+    let critical_temperature = torus.as_ref().and(geometry.critical_temperature(horizontal, vertical)).unwrap_or(0.0);
+    let coolest = if wang_landau.is_some() { 2.0 * app.ising.temperature } else { 2.0 * critical_temperature };
+    let hottest = app.history.iter().map(|&(t, _, _)| t).chain(app.reweighted.iter().map(|p| p.0)).fold(coolest, f64::max);
+    let t_max = if hottest > 0.0 { 1.05 * hottest } else { 1.0 };
+    let curve = |f: &dyn Fn(f64) -> f64| -> Vec<(f64, f64)> {
+        (1..=100).map(|k| t_max * k as f64 / 100.0).map(|t| (t, f(t))).filter(|p| p.1.is_finite()).collect()
+    };
+    let (yang, kaufman, onsager_energy) = match &torus {
+        Some(torus) => (
+            if ferromagnetic { curve(&|t| onsager::spontaneous_magnetization(horizontal, vertical, t)) } else { Vec::new() },
+            curve(&|t| torus.energy(t)),
+            if isotropic { curve(&|t| onsager::energy(horizontal, t)) } else { Vec::new() },
+        ),
+        None => (Vec::new(), Vec::new(), Vec::new()),
+    };
+    let (wang_landau_magnetization, wang_landau_energy) = match &app.density_of_states {
+        Some(density_of_states) => (
+            curve(&|t| density_of_states.thermodynamics(t).absolute_magnetization.unwrap_or(f64::NAN)),
+            curve(&|t| density_of_states.thermodynamics(t).energy),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+    let reweighted_magnetization: Vec<(f64, f64)> = app
+        .reweighted
+        .iter()
+        .filter_map(|(t, estimate)| estimate.absolute_magnetization.map(|m| (*t, m)))
+        .collect();
+    let reweighted_energy: Vec<(f64, f64)> = app.reweighted.iter().map(|(t, estimate)| (*t, estimate.energy)).collect();
+    let reweighted_susceptibility: Vec<(f64, f64)> =
+        app.reweighted.iter().map(|(t, estimate)| (*t, estimate.susceptibility)).collect();
+    let torus_name = torus.as_ref().map_or(String::new(), |torus| format!("Kaufman ({}x{})", torus.width, torus.height));
+
+    let monte_carlo = |points| {
+        Dataset::default()
+            .name("Monte Carlo")
+            .marker(Marker::Dot)
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::White))
+            .data(points)
+    };
+    let exact = |name: String, color: Color, points| {
+        Dataset::default()
+            .name(name)
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(color))
+            .data(points)
+    };
+    let temperature_axis = Axis::default()
+        .title("T")
+        .style(Style::default().fg(Color::DarkGray))
+        .bounds([0.0, t_max])
+        .labels(vec![Span::raw("0"), Span::raw(format!("{:.2}", t_max))]);
+    let chart_block = |title: &'static str| {
+        Block::default()
+            .style(Style::default().fg(Color::White))
+            .borders(Borders::ALL)
+            .title(title)
+    };
+
+    let mut magnetization_datasets = vec![monte_carlo(&magnetization_points)];
+    if !yang.is_empty() {
+        magnetization_datasets.push(exact("Yang (∞)".to_string(), Color::LightYellow, &yang));
+    }
+    if !wang_landau_magnetization.is_empty() {
+        magnetization_datasets.push(exact("Wang-Landau".to_string(), Color::LightGreen, &wang_landau_magnetization));
+    }
+    if !reweighted_magnetization.is_empty() {
+        magnetization_datasets.push(exact("Reweighted".to_string(), Color::LightMagenta, &reweighted_magnetization));
+    }
+    let magnetization_chart = Chart::new(magnetization_datasets)
+        .block(chart_block(" |m| vs T "))
+        .x_axis(temperature_axis.clone())
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([0.0, 1.0])
+                .labels(vec![Span::raw("0"), Span::raw("1")]),
+        );
+
+    let energies = energy_points.iter().chain(kaufman.iter()).chain(onsager_energy.iter()).chain(wang_landau_energy.iter()).chain(reweighted_energy.iter()).map(|p| p.1);
+    let (lowest, highest) = energies.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), e| (lo.min(e), hi.max(e)));
+    let (lowest, highest) = if lowest < highest { (lowest, highest) } else { (-1.0, 1.0) };
+    let mut energy_datasets = vec![monte_carlo(&energy_points)];
+    if !kaufman.is_empty() {
+        energy_datasets.push(exact(torus_name, Color::LightYellow, &kaufman));
+    }
+    if !onsager_energy.is_empty() {
+        energy_datasets.push(exact("Onsager (∞)".to_string(), Color::LightRed, &onsager_energy));
+    }
+    if !wang_landau_energy.is_empty() {
+        energy_datasets.push(exact("Wang-Landau".to_string(), Color::LightGreen, &wang_landau_energy));
+    }
+    if !reweighted_energy.is_empty() {
+        energy_datasets.push(exact("Reweighted".to_string(), Color::LightMagenta, &reweighted_energy));
+    }
+    let energy_chart = Chart::new(energy_datasets)
+        .block(chart_block(" E/N vs T "))
+        .x_axis(temperature_axis)
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([lowest, highest])
+                .labels(vec![Span::raw(format!("{:.2}", lowest)), Span::raw(format!("{:.2}", highest))]),
+        );
+    // Over the temperatures the series reach only, to resolve the peak
+    let peak: Vec<(f64, f64)> = app.susceptibility_peak.into_iter().collect();
+    let susceptibility_chart = (!reweighted_susceptibility.is_empty()).then(|| {
+        let (lo, hi) = (reweighted_susceptibility[0].0, reweighted_susceptibility[reweighted_susceptibility.len() - 1].0);
+        let highest = reweighted_susceptibility.iter().map(|p| p.1).fold(0.0, f64::max);
+        let highest = if highest > 0.0 { 1.05 * highest } else { 1.0 };
+        let peak_marker = Dataset::default()
+            .name("Peak")
+            .marker(Marker::Block)
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::White))
+            .data(&peak);
+        Chart::new(vec![exact("Reweighted".to_string(), Color::LightMagenta, &reweighted_susceptibility), peak_marker])
+            .block(chart_block(" χ vs T (reweighted) "))
+            .x_axis(
+                Axis::default()
+                    .title("T")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .bounds([lo, hi])
+                    .labels(vec![Span::raw(format!("{:.3}", lo)), Span::raw(format!("{:.3}", hi))]),
+            )
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().fg(Color::DarkGray))
+                    .bounds([0.0, highest])
+                    .labels(vec![Span::raw("0"), Span::raw(format!("{:.2}", highest))]),
+            )
+    });
+    let chart_constraints = match susceptibility_chart {
+        Some(_) => vec![Constraint::Ratio(1, 3); 3],
+        None => vec![Constraint::Percentage(50), Constraint::Percentage(50)],
+    };
+    let chart_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(chart_constraints)
+        .split(panels_chunk[6]);
 
     //
     // Footer
@@ -135,9 +632,38 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
 
     let footer_block = Block::default().style(Style::default().bg(Color::DarkGray));
 
+    let mut key_hints = String::from("  Quit: (q), Pause: <space>");
+    if app.planar.is_some() {
+        key_hints.push_str(", Overrelaxation: (u)");
+    } else if app.heisenberg.is_some() {
+        key_hints.push_str(", Color by z/angle: (v), Heat bath: (u)");
+    } else {
+        key_hints.push_str(", View: (v), Update: (u)");
+    }
+    if app.has_ising_spins() && app.ising.model == Model::Ising && app.tempering.is_none() && app.density_of_states.is_none() {
+        key_hints.push_str(", Field sweep: (a)");
+    }
+    if app.tempering.is_some() {
+        key_hints.push_str(", Randomize replicas: (r)");
+    }
+    if app.density_of_states.is_some() {
+        key_hints.push_str(", Restart walk: (r), Export g(E): (x)");
+    } else if app.recorder.samples() > 0 {
+        key_hints.push_str(", Export series: (x)");
+    }
+    if app.schedule.is_some() {
+        key_hints.push_str(", Schedule: (p) / (r)");
+    }
+    if app.replica.is_some() || app.random_field.is_some() || app.dilution > 0.0 {
+        key_hints.push_str(", Reseed disorder: (d)");
+    }
+    if app.ising.geometry.depth() > 1 {
+        key_hints.push_str(", Slice: ([) / (])");
+    }
+
     let footer_page = match app.page {
         Page::Main => Paragraph::new(Text::styled(
-            "  Quit: (q), Pause: <space>",
+            key_hints.clone(),
             Style::default().fg(Color::DarkGray),
         )),
         Page::Exit => Paragraph::new(Text::styled(
@@ -172,7 +698,7 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     let footer_chunk = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Length(55),
+            Constraint::Length(key_hints.chars().count() as u16 + 2),
             Constraint::Min(2),
             Constraint::Length(30),
             Constraint::Length(14),
@@ -180,12 +706,45 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         .split(chunks[3]);
 
     frame.render_widget(title, chunks[0]);
-    frame.render_widget(ising_canvas, sim_chunks[0]);
-    frame.render_widget(temp_control, controls_chunk[0]);
-    frame.render_widget(coupling_control, controls_chunk[1]);
-    frame.render_widget(mag_moment_control, controls_chunk[2]);
-    frame.render_widget(mag_field_strength_control, controls_chunk[3]);
-    // frame.render_widget(magnetization_chart, controls_chunk[4]);
+    if let Some(tempering) = &app.tempering {
+        render_replicas(frame, tempering, app.slice, sim_chunks[0]);
+    } else if let Some(degree_chart) = degree_chart {
+        frame.render_widget(degree_chart, sim_chunks[0]);
+    } else {
+        frame.render_widget(ising_canvas, sim_chunks[0]);
+    }
+    for (parameter, area) in parameters.iter().zip(controls_chunk.iter()) {
+        frame.render_widget(parameter_gauge(app, *parameter), *area);
+    }
+    frame.render_widget(stats, panels_chunk[0]);
+    if let Some(schedule_progress) = schedule_progress {
+        frame.render_widget(schedule_progress, panels_chunk[1]);
+    }
+    if app.replica.is_some() {
+        frame.render_widget(overlap_chart, panels_chunk[2]);
+    }
+    if let Some(avalanche_chart) = avalanche_chart {
+        frame.render_widget(avalanche_chart, panels_chunk[3]);
+    }
+    if let Some(acceptance_chart) = acceptance_chart {
+        frame.render_widget(acceptance_chart, panels_chunk[4]);
+    }
+    if let Some((histogram_chart, ln_g_chart)) = wang_landau_charts {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(panels_chunk[5]);
+        frame.render_widget(histogram_chart, chunks[0]);
+        frame.render_widget(ln_g_chart, chunks[1]);
+    }
+    // Too short to read below a few rows
+    if panels_chunk[6].height >= 8 {
+        frame.render_widget(magnetization_chart, chart_chunks[0]);
+        frame.render_widget(energy_chart, chart_chunks[1]);
+        if let Some(susceptibility_chart) = susceptibility_chart {
+            frame.render_widget(susceptibility_chart, chart_chunks[2]);
+        }
+    }
     frame.render_widget(footer_page, footer_chunk[0]);
     frame.render_widget(footer_spacing, footer_chunk[1]);
     frame.render_widget(footer_info_label, footer_chunk[2]);
@@ -213,6 +772,219 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     }
 }
 
+/// Renders the lattice as colored half blocks in the footprint of the braille view, shrunk
+/// `stride` times. Each cell stacks two pixels, and each pixel shows the top left site of
+/// its 2x2 block, in the color of its occupied site.
+fn lattice_as_blocks<F: Fn(usize) -> Color>(ising: &Ising, slice: usize, stride: usize, color: F) -> Text<'static> {
+    let canvas = ising.canvas(slice);
+    let pixel_color = |x: usize, y: usize| {
+        if x >= canvas.width || y >= canvas.height {
+            return Color::Reset;
+        }
+        canvas.sites[y * canvas.width + x].map_or(Color::Reset, |idx| {
+            if ising.occupancy[idx] {
+                color(idx)
+            } else {
+                Color::Black
+            }
+        })
+    };
+
+    let lines: Vec<Line> = (0..canvas.height.div_ceil(4 * stride))
+        .map(|row| {
+            let spans: Vec<Span> = (0..canvas.width.div_ceil(2 * stride))
+                .map(|col| {
+                    let top = pixel_color(2 * stride * col, 4 * stride * row);
+                    let bottom = pixel_color(2 * stride * col, 2 * stride * (2 * row + 1));
+                    Span::styled("▀", Style::default().fg(top).bg(bottom))
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+    Text::from(lines)
+}
+
+/// Draws every replica of parallel tempering shrunk into a grid of panels over `area`,
+/// coldest first, each titled with its temperature.
+fn render_replicas(frame: &mut Frame, tempering: &ParallelTempering, slice: usize, area: Rect) {
+    let count = tempering.replicas.len();
+    let columns = (count as f64).sqrt().ceil() as usize;
+    let (width, height) = tempering.replicas[0].canvas_size();
+    let panel_width = width.div_ceil(2 * columns) as u16 + 1;
+    let panel_height = height.div_ceil(4 * columns) as u16 + 1;
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(panel_height); count.div_ceil(columns)])
+        .split(area);
+    for (row, row_area) in rows.iter().enumerate() {
+        let panels = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Length(panel_width); columns])
+            .split(*row_area);
+        for (column, panel_area) in panels.iter().enumerate() {
+            let i = row * columns + column;
+            let Some(replica) = tempering.replicas.get(i) else {
+                break;
+            };
+            let blocks = lattice_as_blocks(replica, slice, columns, |idx| spin_color(replica.model, replica.lattice[idx]));
+            let panel = Paragraph::new(blocks).block(
+                Block::default()
+                    .style(Style::default().fg(Color::White))
+                    .title(format!("T={:.3}", tempering.temperatures[i])),
+            );
+            frame.render_widget(panel, *panel_area);
+        }
+    }
+}
+
+/// Most degree classes shown, the last one collecting every higher degree.
+const DEGREE_CLASSES: usize = 16;
+
+/// (label, number of sites, magnetization) of each degree, in increasing order.
+fn degree_classes(ising: &Ising) -> Vec<(String, u64, f64)> {
+    let mut classes: BTreeMap<usize, (u64, i64)> = BTreeMap::new();
+    for (bonds, &spin) in ising.bonds.iter().zip(ising.lattice.iter()) {
+        let class = classes.entry(bonds.len()).or_default();
+        class.0 += 1;
+        class.1 += spin as i64;
+    }
+
+    let mut merged: Vec<(String, u64, i64)> = Vec::new();
+    for (i, (degree, (count, spins))) in classes.into_iter().enumerate() {
+        if i < DEGREE_CLASSES {
+            merged.push((degree.to_string(), count, spins));
+        } else {
+            let last = merged.last_mut().unwrap();
+            if !last.0.starts_with('≥') {
+                last.0 = format!("≥{}", last.0);
+            }
+            last.1 += count;
+            last.2 += spins;
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(label, count, spins)| (label, count, spins as f64 / count as f64))
+        .collect()
+}
+
+/// Color of an occupied site.
+fn spin_color(model: Model, spin: i8) -> Color {
+    match (model, spin) {
+        (Model::Potts { states }, _) => state_color(spin, states),
+        (_, 1) => Color::White,
+        (_, -1) => Color::Blue,
+        _ => Color::DarkGray,
+    }
+}
+
+/// Color of Potts state `state` of `states`, at evenly spaced hues.
+fn state_color(state: i8, states: i8) -> Color {
+    hue_color((state - 1) as f64 / states as f64)
+}
+
+/// Fully saturated color of a hue in turns, 0 being red.
+fn hue_color(turns: f64) -> Color {
+    let hue = 6.0 * turns;
+    let ramp = |offset: f64| {
+        let distance = ((hue - offset).rem_euclid(6.0) - 3.0).abs();
+        ((distance - 1.0).clamp(0.0, 1.0) * 255.0) as u8
+    };
+    Color::Rgb(ramp(0.0), ramp(2.0), ramp(4.0))
+}
+
+/// Gauge for one of the adjustable parameters, highlighted when selected.
+fn parameter_gauge(app: &App, parameter: ParameterType) -> Gauge<'static> {
+    let (title, param, value, color) = match parameter {
+        ParameterType::Temp => (" Temperature (T) ", &app.temp_param, app.ising.temperature, Color::Red),
+        ParameterType::HorizontalCoupling => (
+            " Horizontal coupling (J_x) ",
+            &app.horizontal_coupling_param,
+            app.ising.horizontal_coupling,
+            Color::LightGreen,
+        ),
+        ParameterType::VerticalCoupling => (
+            " Vertical coupling (J_y) ",
+            &app.vertical_coupling_param,
+            app.ising.vertical_coupling,
+            Color::Green,
+        ),
+        ParameterType::DiagonalCoupling => (
+            " Diagonal coupling (J_2) ",
+            app.diagonal_coupling_param.as_ref().expect("listed without J2 bonds"),
+            app.ising.diagonal_coupling,
+            Color::LightBlue,
+        ),
+        ParameterType::AxialCoupling => (
+            " Axial coupling (J_3) ",
+            app.axial_coupling_param.as_ref().expect("listed without J3 bonds"),
+            app.ising.axial_coupling,
+            Color::Blue,
+        ),
+        ParameterType::CrystalField => (
+            " Crystal field (D) ",
+            app.crystal_field_param.as_ref().expect("listed without Blume-Capel spins"),
+            app.ising.crystal_field,
+            Color::LightRed,
+        ),
+        ParameterType::Anisotropy => (
+            " Single-ion anisotropy (D) ",
+            app.anisotropy_param.as_ref().expect("listed without Heisenberg spins"),
+            app.heisenberg.as_ref().map_or(0.0, |heisenberg| heisenberg.anisotropy),
+            Color::LightRed,
+        ),
+        ParameterType::MagMoment => (
+            " Magnetic moment (μ) ",
+            &app.mag_moment_param,
+            app.ising.magnetic_moment,
+            Color::LightMagenta,
+        ),
+        ParameterType::MagFieldStrength => (
+            " Magnetic field strength (B) ",
+            &app.mag_field_strength_param,
+            app.ising.magnetic_field_strength,
+            Color::Cyan,
+        ),
+        ParameterType::Disorder => (
+            " Random field strength (Δ) ",
+            &app.disorder_param,
+            app.disorder_param.scaled(),
+            Color::LightYellow,
+        ),
+    };
+
+    let label = match parameter {
+        ParameterType::Temp if app.field_sweep.is_some() => "0 (field sweep)".to_string(),
+        ParameterType::Temp if app.tempering.is_some() => {
+            let temperatures = &app.tempering.as_ref().expect("guarded").temperatures;
+            format!("{:.3}-{:.3} (tempering)", temperatures[0], temperatures[temperatures.len() - 1])
+        }
+        ParameterType::MagMoment | ParameterType::MagFieldStrength if app.field_sweep.is_some() => {
+            format!("{:.3} (field sweep)", value)
+        }
+        _ => format!("{:.3}", value),
+    };
+
+    let border_color = if parameter == app.current_parameter {
+        Color::Yellow
+    } else {
+        Color::White
+    };
+
+    Gauge::default()
+        .block(
+            Block::default()
+                .style(Style::default().fg(border_color))
+                .borders(Borders::ALL)
+                .title(title),
+        )
+        .gauge_style(Style::default().bg(Color::DarkGray).fg(color))
+        .percent((param.normalized() * 100.0) as u16)
+        .label(label)
+}
+
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     // Cut the given rectangle into three vertical pieces
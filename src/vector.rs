@@ -3,16 +3,8 @@ pub struct Vec2 {
     pub y: i32,
 }
 
-pub fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>> {
-    assert!(!v.is_empty());
-    let len = v[0].len();
-    let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
-    (0..len)
-        .map(|_| {
-            iters
-                .iter_mut()
-                .map(|n| n.next().unwrap())
-                .collect::<Vec<T>>()
-        })
-        .collect()
+pub struct Vec3 {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
 }
@@ -0,0 +1,297 @@
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::exact::{self, Thermodynamics};
+use crate::ising::{Ising, Model};
+
+/// Resolution at which energies count as distinct levels without binning.
+pub const LEVEL_RESOLUTION: f64 = 1e-6;
+
+/// Wang-Landau random walk in energy, refined until ln f falls below `tolerance`.
+#[derive(Debug, Clone, Copy)]
+pub struct WangLandau {
+    /// Fraction of the mean number of visits every level needs for the histogram to count
+    /// as flat.
+    pub flatness: f64,
+    pub tolerance: f64,
+    /// Width of the energy bins, or 0 for one level per distinct energy, which suits
+    /// couplings and fields taking few values.
+    pub bin_width: f64,
+}
+
+impl Default for WangLandau {
+    fn default() -> Self {
+        Self {
+            flatness: 0.8,
+            tolerance: 1e-6,
+            bin_width: 0.0,
+        }
+    }
+}
+
+impl WangLandau {
+    /// Walks over the energies of `ising` until converged.
+    pub fn run<R: Rng + ?Sized>(&self, ising: &Ising, rng: &mut R) -> DensityOfStates {
+        let mut density_of_states = DensityOfStates::new(ising, *self, rng);
+        while !density_of_states.is_converged() {
+            density_of_states.sweep(rng);
+        }
+        density_of_states
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Level {
+    ln_g: f64,
+    /// Visits since the last refinement.
+    visits: u64,
+    /// Visits over the whole walk, and their sums of E, M, |M| and M² for microcanonical
+    /// averages, which do not depend on the weights of the walk.
+    samples: u64,
+    sums: [f64; 4],
+}
+
+/// Density of states g(E) of the interaction energy (bonds, local and wall fields) of a
+/// system, without the uniform field. The walk accepts a move from E to E' with probability
+/// g(E) / g(E') and multiplies g of the level it is at by f after every attempt. Whenever
+/// the histogram of visits is flat, it is cleared and ln f halved, so g(E) converges to
+/// the density of states up to a constant, fixed by the total number of configurations.
+#[derive(Debug)]
+pub struct DensityOfStates {
+    pub settings: WangLandau,
+    /// Configuration of the walk.
+    pub walker: Ising,
+    energy: f64,
+    magnetization: i64,
+    levels: BTreeMap<i64, Level>,
+    ln_f: f64,
+    refinements: usize,
+    sweeps: usize,
+    /// Couplings, fields and occupancy walked with, to tell when they have changed.
+    conditions: Vec<f64>,
+}
+
+impl DensityOfStates {
+    pub fn new<R: Rng + ?Sized>(ising: &Ising, settings: WangLandau, rng: &mut R) -> Self {
+        let mut walker = ising.clone();
+        walker.magnetic_field_strength = 0.0;
+        walker.randomize(rng);
+        let energy = walker.total_energy();
+        let magnetization = walker.lattice.iter().map(|&s| s as i64).sum();
+        let mut density_of_states = Self {
+            settings,
+            walker,
+            energy,
+            magnetization,
+            levels: BTreeMap::new(),
+            ln_f: 1.0,
+            refinements: 0,
+            sweeps: 0,
+            conditions: Self::conditions(ising),
+        };
+        density_of_states.level(energy);
+        density_of_states
+    }
+
+    /// Whether `ising` still has the couplings, fields and vacancies walked with.
+    pub fn matches(&self, ising: &Ising) -> bool {
+        self.conditions == Self::conditions(ising)
+    }
+
+    fn conditions(ising: &Ising) -> Vec<f64> {
+        let mut conditions = exact::conditions(ising);
+        conditions.push(ising.crystal_field);
+        conditions
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.ln_f < self.settings.tolerance
+    }
+
+    pub fn ln_f(&self) -> f64 {
+        self.ln_f
+    }
+
+    /// Number of times ln f has been halved.
+    pub fn refinements(&self) -> usize {
+        self.refinements
+    }
+
+    pub fn sweeps(&self) -> usize {
+        self.sweeps
+    }
+
+    /// Lowest over mean number of visits since the last refinement.
+    pub fn flatness(&self) -> f64 {
+        let visits = self.levels.values().map(|level| level.visits);
+        let (lowest, total) = visits.fold((u64::MAX, 0), |(lowest, total), v| (lowest.min(v), total + v));
+        let mean = total as f64 / self.levels.len() as f64;
+        if mean > 0.0 {
+            lowest as f64 / mean
+        } else {
+            0.0
+        }
+    }
+
+    fn key(&self, energy: f64) -> i64 {
+        let width = if self.settings.bin_width > 0.0 { self.settings.bin_width } else { LEVEL_RESOLUTION };
+        (energy / width).round() as i64
+    }
+
+    /// Level of `energy`, which a level found for the first time enters at the lowest ln g
+    /// so far, rather than 0, so the walk does not linger there.
+    fn level(&mut self, energy: f64) -> &mut Level {
+        let key = self.key(energy);
+        if !self.levels.contains_key(&key) {
+            let lowest = self.levels.values().map(|level| level.ln_g).fold(f64::INFINITY, f64::min);
+            let ln_g = if lowest.is_finite() { lowest } else { 0.0 };
+            self.levels.insert(key, Level { ln_g, ..Level::default() });
+        }
+        self.levels.get_mut(&key).expect("inserted")
+    }
+
+    /// One attempted move per site, then a refinement if the histogram is flat. Nothing
+    /// once converged.
+    pub fn sweep<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        if self.is_converged() {
+            return;
+        }
+        let num_sites = self.walker.lattice.len();
+        for _ in 0..num_sites {
+            let idx = rng.gen_range(0..num_sites);
+            if self.walker.occupancy[idx] {
+                let spin = self.walker.lattice[idx];
+                let new_spin = self.walker.propose(spin, rng);
+                let diff = self.walker.site_energy(idx, new_spin) - self.walker.site_energy(idx, spin);
+                let ln_g_new = self.level(self.energy + diff).ln_g;
+                let ln_g = self.level(self.energy).ln_g;
+                if ln_g_new <= ln_g || rng.gen::<f64>() < (ln_g - ln_g_new).exp() {
+                    self.walker.lattice[idx] = new_spin;
+                    self.energy += diff;
+                    self.magnetization += (new_spin - spin) as i64;
+                }
+            }
+
+            let (ln_f, energy, m) = (self.ln_f, self.energy, self.magnetization as f64);
+            let level = self.level(energy);
+            level.ln_g += ln_f;
+            level.visits += 1;
+            level.samples += 1;
+            for (sum, value) in level.sums.iter_mut().zip([energy, m, m.abs(), m * m]) {
+                *sum += value;
+            }
+        }
+        self.sweeps += 1;
+
+        if self.flatness() >= self.settings.flatness {
+            self.ln_f /= 2.0;
+            self.refinements += 1;
+            self.levels.values_mut().for_each(|level| level.visits = 0);
+            // Clears the rounding accumulated over the stage
+            self.energy = self.walker.total_energy();
+        }
+    }
+
+    /// ln g(E) of every level visited, lowest energy first, normalized to the number of
+    /// configurations, with E the mean energy seen at the level.
+    pub fn ln_g(&self) -> Vec<(f64, f64)> {
+        let visited: Vec<(f64, f64)> = self
+            .levels
+            .values()
+            .filter(|level| level.samples > 0)
+            .map(|level| (level.sums[0] / level.samples as f64, level.ln_g))
+            .collect();
+        let largest = visited.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+        let ln_total = largest + visited.iter().map(|p| (p.1 - largest).exp()).sum::<f64>().ln();
+        let offset = self.walker.num_occupied() as f64 * (self.states() as f64).ln() - ln_total;
+        visited.into_iter().map(|(energy, ln_g)| (energy, ln_g + offset)).collect()
+    }
+
+    /// (E, visits since the last refinement) of every level found.
+    pub fn histogram(&self) -> Vec<(f64, u64)> {
+        let width = if self.settings.bin_width > 0.0 { self.settings.bin_width } else { LEVEL_RESOLUTION };
+        self.levels.iter().map(|(&key, level)| (key as f64 * width, level.visits)).collect()
+    }
+
+    /// Values a spin takes.
+    fn states(&self) -> usize {
+        match self.walker.model {
+            Model::Ising => 2,
+            Model::BlumeCapel => 3,
+            Model::Potts { states } => states as usize,
+        }
+    }
+
+    /// Thermodynamics in zero uniform field from the canonical sum over levels, with
+    /// magnetizations from their microcanonical averages. Those are not kept for Potts
+    /// spins, which get NaN.
+    pub fn thermodynamics(&self, temperature: f64) -> Thermodynamics {
+        let ln_g = self.ln_g();
+        let lowest = ln_g.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+        // Written so that T = 0 keeps the ground states without dividing 0 by 0
+        let ln_weights: Vec<f64> = ln_g
+            .iter()
+            .map(|&(energy, ln_g)| match energy <= lowest {
+                true => ln_g,
+                false => ln_g - (energy - lowest) / temperature,
+            })
+            .collect();
+        let largest = ln_weights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let (mut z, mut sums) = (0.0, [0.0; 5]);
+        let levels = self.levels.values().filter(|level| level.samples > 0);
+        for (level, &ln_weight) in levels.zip(ln_weights.iter()) {
+            let weight = (ln_weight - largest).exp();
+            let [energy, m, abs_m, m2] = level.sums.map(|sum| sum / level.samples as f64);
+            z += weight;
+            for (sum, value) in sums.iter_mut().zip([energy, energy * energy, m, abs_m, m2]) {
+                *sum += weight * value;
+            }
+        }
+        let n = self.walker.num_occupied() as f64;
+        let [e, e2, m, abs_m, m2] = sums.map(|sum| sum / z);
+        let potts = matches!(self.walker.model, Model::Potts { .. });
+        Thermodynamics {
+            free_energy: (lowest - temperature * (largest + z.ln())) / n,
+            energy: e / n,
+            magnetization: if potts { f64::NAN } else { m / n },
+            absolute_magnetization: (!potts).then_some(abs_m / n),
+            specific_heat: exact::fluctuation(e2 - e * e, n * temperature * temperature),
+            susceptibility: if potts { f64::NAN } else { exact::fluctuation(m2 - abs_m * abs_m, n * temperature) },
+        }
+    }
+
+    /// Writes 'E ln_g(E)' lines, lowest energy first.
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let lines: String = self.ln_g().iter().map(|(energy, ln_g)| format!("{} {}\n", energy, ln_g)).collect();
+        fs::write(path, lines).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exact::Enumeration;
+    use crate::lattice::Rectangular;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::rc::Rc;
+
+    #[test]
+    fn matches_enumeration_of_the_4x4_torus() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let ising = Ising::new(Rc::new(Rectangular::square(4)), &mut rng);
+        let density_of_states = WangLandau::default().run(&ising, &mut rng);
+        let enumeration = Enumeration::new(&ising).unwrap();
+        // -32 to 32 in steps of 4, without the single flips of -28 and 28
+        assert_eq!(density_of_states.ln_g().len(), 15);
+        // The error of ln g saturates near a per cent, whichever ln f the walk ends at
+        for temperature in [1.5, 2.269, 3.5] {
+            let estimate = density_of_states.thermodynamics(temperature);
+            let exact = enumeration.thermodynamics(temperature, 0.0);
+            assert!((estimate.free_energy - exact.free_energy).abs() < 0.05);
+            assert!((estimate.energy - exact.energy).abs() < 0.05);
+            assert!((estimate.absolute_magnetization.unwrap() - exact.absolute_magnetization.unwrap()).abs() < 0.05);
+            assert!((estimate.specific_heat - exact.specific_heat).abs() < 0.1);
+        }
+    }
+}